@@ -1,21 +1,51 @@
 use std::collections::HashMap;
 
 use crate::error::ContractError;
+use crate::migration::migrate_v0_1;
 use crate::msg::{
-    ConfigResponse, ExecuteMsg, InstantiateMsg, QueryMsg, StakerInfoResponse, StateResponse, Unlock,
+    AllStakersResponse, ConfigResponse, Cw20HookMsg, ExecuteMsg, GameResponse, GamesResponse,
+    InstantiateMsg, MigrateMsg, OwnershipResponse, PauseStatusResponse, PositionResponse,
+    PositionsResponse, QueryMsg, RewardFunding, RewardFundingResponse, ScheduleResponse,
+    SchedulesResponse, StakedBalanceAtHeightResponse, StakerInfoResponse, StakerResponse,
+    StateResponse, TopStakersResponse, TotalStakedAtHeightResponse, Unlock,
 };
 use crate::state::{
-    load_state, remove_user_staking, store_state, store_user_staking, user_staking, Config,
-    StakerInfo, State, CONFIG, STATE,
+    add_reward_index, all_reward_totals, burn_receipt, insert_nonzero, load_state, mint_receipt,
+    position, position_nft, remove_position, remove_user_staking, reward_indexes, reward_totals,
+    snapshot_stake, store_new_schedule, store_position, store_state, store_user_staking,
+    update_reward_totals, user_staking, AssetInfo, Boost, Config, EmissionCurve, GameInfo,
+    LockExpiry, LockTier, PauseFlags, PendingOwner, Schedule, StakeLock, StakerInfo, State,
+    TokenType, CONFIG, EMERGENCY_WITHDRAW, EXPIRY_INDEX, GAMES, LOCK_EXPIRIES, OWNER, PAUSED,
+    PAUSER, PENDING_OWNER, POSITIONS, POSITION_SEQ, SCHEDULES, STAKED_BALANCES, STAKED_TOTAL,
+    USER_STAKING,
 };
 
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    to_json_binary, Addr, BankMsg, Binary, Coin, Decimal, Deps, DepsMut, Env, MessageInfo,
-    Response, StdResult, Uint128,
+    from_json, to_json_binary, Addr, Api, BankMsg, Binary, Coin, CosmosMsg, Decimal, Deps, DepsMut,
+    Empty, Env, MessageInfo, Order, Response, StdResult, Storage, Uint128,
 };
-use cw2::set_contract_version;
+use cw2::{get_contract_version, set_contract_version};
+use cw20::{BalanceResponse, Cw20QueryMsg, Cw20ReceiveMsg};
+use cw20_base::allowances::{
+    execute_decrease_allowance, execute_increase_allowance, execute_send_from,
+    execute_transfer_from, query_allowance,
+};
+use cw20_base::contract::{execute_send, execute_transfer, query_balance, query_token_info};
+use cw20_base::msg::InstantiateMsg as Cw20InstantiateMsg;
+use cw721::{Cw721Execute, Cw721ReceiveMsg};
+use cw721_base::{
+    ExecuteMsg as Cw721ExecuteMsg, Extension, InstantiateMsg as Cw721InstantiateMsg, MintMsg,
+    QueryMsg as Cw721QueryMsg,
+};
+use cw_storage_plus::Bound;
+use cw_utils::{must_pay, one_coin, Expiration};
+use semver::Version;
+
+// pagination of list queries
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 30;
 
 // version info for migration info
 const CONTRACT_NAME: &str = "crates.io:kartel_staking";
@@ -23,37 +53,135 @@ const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
-    deps: DepsMut,
-    _env: Env,
+    mut deps: DepsMut,
+    env: Env,
     info: MessageInfo,
     msg: InstantiateMsg,
 ) -> Result<Response, ContractError> {
-    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
-
-    CONFIG.save(
-        deps.storage,
-        &Config {
-            owner: info.sender,
-            kart_denom: msg.kart_denom,
-            unlock_time: msg.unlock_time,
-            distribution_schedule: msg.distribution_schedule,
+    // the contract is itself the sKART receipt token, minting is done on stake
+    cw20_base::contract::instantiate(
+        deps.branch(),
+        env.clone(),
+        info.clone(),
+        Cw20InstantiateMsg {
+            name: msg.receipt_name,
+            symbol: msg.receipt_symbol,
+            decimals: msg.receipt_decimals,
+            initial_balances: vec![],
+            mint: None,
+            marketing: None,
         },
     )?;
 
-    STATE.save(
+    if let Some(position_nft_info) = &msg.position_nft {
+        position_nft().instantiate(
+            deps.branch(),
+            env.clone(),
+            info.clone(),
+            Cw721InstantiateMsg {
+                name: position_nft_info.name.clone(),
+                symbol: position_nft_info.symbol.clone(),
+                minter: env.contract.address.to_string(),
+            },
+        )?;
+    }
+
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    OWNER.set(deps.branch(), Some(info.sender.clone()))?;
+    let pauser = msg
+        .pauser
+        .as_ref()
+        .map(|pauser| deps.api.addr_validate(pauser))
+        .transpose()?;
+    PAUSER.set(deps.branch(), pauser)?;
+    PAUSED.save(deps.storage, &PauseFlags::default())?;
+    EMERGENCY_WITHDRAW.save(deps.storage, &false)?;
+
+    let config = Config {
+        kart_denom: msg.kart_denom,
+        kart_token_type: msg.kart_token_type,
+        unlock_time: msg.unlock_time,
+        drawdown_epoch: msg.drawdown_epoch,
+        max_drawdown: msg.max_drawdown,
+        position_nft: msg.position_nft.is_some(),
+        boost_collections: msg
+            .boost_collections
+            .into_iter()
+            .map(|(collection, multiplier)| (Addr::unchecked(collection), multiplier))
+            .collect(),
+        lock_tiers: msg.lock_tiers,
+        instant_unstake_penalty: msg.instant_unstake_penalty,
+        penalty_treasury: msg.penalty_treasury.map(Addr::unchecked),
+        penalty_decay: msg.penalty_decay,
+    };
+    validate_config(deps.api, &config)?;
+
+    // initial schedules are linear, their rewards are owed to the stakers like later ones
+    for s in msg.distribution_schedule {
+        let schedule = Schedule {
+            start_date: s.0,
+            end_date: s.1,
+            amount: s.2,
+            asset: validate_asset(deps.api, s.3)?,
+            curve: EmissionCurve::Linear,
+            rebased_at: s.0,
+        };
+        update_reward_totals(deps.storage, &schedule.asset.to_string(), |totals| {
+            totals.distributed += schedule.amount
+        })?;
+        store_new_schedule(deps.storage, &schedule)?;
+    }
+
+    CONFIG.save(deps.storage, &config)?;
+
+    store_state(
         deps.storage,
         &State {
             total_staker: 0,
             total_stake_amount: Uint128::zero(),
+            total_share: Uint128::zero(),
+            total_weight: Uint128::zero(),
             last_distributed: 0,
-            reward_index: HashMap::new(),
-            reward_distributed: HashMap::new(),
+            epoch_start: 0,
+            epoch_bankroll: Uint128::zero(),
+            epoch_drawdown: Uint128::zero(),
+            next_unlock_id: 0,
+            total_unlock: Uint128::zero(),
+            total_unlock_share: Uint128::zero(),
         },
     )?;
 
     Ok(Response::default())
 }
 
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(mut deps: DepsMut, env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
+    let stored = get_contract_version(deps.storage)?;
+    let from: Version = stored.version.parse()?;
+    let to: Version = CONTRACT_VERSION.parse()?;
+
+    if stored.contract != CONTRACT_NAME || from > to {
+        return Err(ContractError::InvalidMigration {
+            contract: stored.contract,
+            version: stored.version,
+        });
+    }
+
+    // data migrations run in order, each for the versions before its layout
+    if from < Version::new(0, 2, 0) {
+        migrate_v0_1(deps.branch(), &env, msg.receipt)?;
+    }
+
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "migrate"),
+        ("from_version", stored.version.as_str()),
+        ("to_version", CONTRACT_VERSION),
+    ]))
+}
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn execute(
     deps: DepsMut,
@@ -61,61 +189,342 @@ pub fn execute(
     info: MessageInfo,
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
+    check_paused(deps.as_ref(), &msg)?;
+
     match msg {
-        ExecuteMsg::Stake {} => stake(deps, env, info),
+        ExecuteMsg::Stake { lock_tier } => stake_native(deps, env, info, lock_tier),
+        ExecuteMsg::Receive(msg) => receive_cw20(deps, env, info, msg),
         ExecuteMsg::Unstake { amount } => unstake(deps, env, info.sender, amount),
+        ExecuteMsg::InstantUnstake { amount } => instant_unstake(deps, env, info, amount),
+        ExecuteMsg::Rebond { id } => rebond(deps, env, info, Some(id)),
+        ExecuteMsg::RebondAll {} => rebond(deps, env, info, None),
         ExecuteMsg::Claim {} => claim_reward(deps, env, info),
-        ExecuteMsg::Withdraw { id } => withdraw(deps, env, info, id),
-        ExecuteMsg::Unlock { amount , denom} => unlock(deps, env, info, amount, denom),
+        ExecuteMsg::Compound {} => compound(deps, env, info),
+        ExecuteMsg::SetAutoCompound { enabled } => set_auto_compound(deps, env, info, enabled),
+        ExecuteMsg::Withdraw { id } => withdraw(deps, env, info, Some(id)),
+        ExecuteMsg::WithdrawAll {} => withdraw(deps, env, info, None),
+        ExecuteMsg::RecoverFunds { asset, amount } => recover_funds(deps, env, info, asset, amount),
         ExecuteMsg::SetDistribution {
-            reward_denom,
+            reward_asset,
             start_date,
             end_date,
             amount,
-        } => set_distribution_schedule(deps, env, info, reward_denom, start_date, end_date, amount),
+            curve,
+        } => set_distribution_schedule(
+            deps,
+            info,
+            reward_asset,
+            start_date,
+            end_date,
+            amount,
+            curve,
+        ),
+        ExecuteMsg::ExtendSchedule { id, end_date } => {
+            extend_schedule(deps, env, info, id, end_date)
+        }
+        ExecuteMsg::TopUpSchedule { id, amount } => top_up_schedule(deps, env, info, id, amount),
+        ExecuteMsg::CancelSchedule { id } => cancel_schedule(deps, env, info, id),
+        ExecuteMsg::ReclaimUndistributed { denom } => reclaim_undistributed(deps, env, info, denom),
+        ExecuteMsg::RescheduleUndistributed {
+            denom,
+            start_date,
+            end_date,
+            curve,
+        } => reschedule_undistributed(deps, env, info, denom, start_date, end_date, curve),
         ExecuteMsg::UpdateConfig { config } => update_config(deps, env, info, config),
+        ExecuteMsg::ProposeNewOwner { owner, expiry } => {
+            propose_new_owner(deps, env, info, owner, expiry)
+        }
+        ExecuteMsg::AcceptOwnership {} => accept_ownership(deps, env, info),
+        ExecuteMsg::CancelOwnershipProposal {} => cancel_ownership_proposal(deps, info),
+        ExecuteMsg::SetPauser { pauser } => set_pauser(deps, info, pauser),
+        ExecuteMsg::SetPaused { paused } => set_paused(deps, info, paused),
+        ExecuteMsg::SetEmergencyWithdraw { enabled } => set_emergency_withdraw(deps, info, enabled),
+        ExecuteMsg::EmergencyWithdraw {} => emergency_withdraw(deps, env, info),
+        ExecuteMsg::EmergencyWithdrawPosition { token_id } => {
+            emergency_withdraw_position(deps, env, info, token_id)
+        }
+        ExecuteMsg::AddGame { game, max_exposure } => add_game(deps, env, info, game, max_exposure),
+        ExecuteMsg::UpdateGame { game, max_exposure } => {
+            update_game(deps, env, info, game, max_exposure)
+        }
+        ExecuteMsg::RemoveGame { game } => remove_game(deps, env, info, game),
+        ExecuteMsg::DepositRevenue {} => {
+            let revenue = info
+                .funds
+                .into_iter()
+                .map(|coin| (coin.denom, coin.amount))
+                .collect();
+            deposit_revenue(deps, env, info.sender, revenue)
+        }
+        ExecuteMsg::Payout { recipient, amount } => payout(deps, env, info, recipient, amount),
+        ExecuteMsg::Transfer { recipient, amount } => {
+            transfer_receipt(deps, env, info, recipient, amount)
+        }
+        ExecuteMsg::Send {
+            contract,
+            amount,
+            msg,
+        } => send_receipt(deps, env, info, contract, amount, msg),
+        ExecuteMsg::IncreaseAllowance {
+            spender,
+            amount,
+            expires,
+        } => Ok(execute_increase_allowance(
+            deps, env, info, spender, amount, expires,
+        )?),
+        ExecuteMsg::DecreaseAllowance {
+            spender,
+            amount,
+            expires,
+        } => Ok(execute_decrease_allowance(
+            deps, env, info, spender, amount, expires,
+        )?),
+        ExecuteMsg::TransferFrom {
+            owner,
+            recipient,
+            amount,
+        } => transfer_receipt_from(deps, env, info, owner, recipient, amount),
+        ExecuteMsg::SendFrom {
+            owner,
+            contract,
+            amount,
+            msg,
+        } => send_receipt_from(deps, env, info, owner, contract, amount, msg),
+        ExecuteMsg::UnstakePosition { token_id, amount } => {
+            unstake_position(deps, env, info, token_id, amount)
+        }
+        ExecuteMsg::InstantUnstakePosition { token_id, amount } => {
+            instant_unstake_position(deps, env, info, token_id, amount)
+        }
+        ExecuteMsg::WithdrawPosition { token_id, id } => {
+            withdraw_position(deps, env, info, token_id, Some(id))
+        }
+        ExecuteMsg::WithdrawAllPosition { token_id } => {
+            withdraw_position(deps, env, info, token_id, None)
+        }
+        ExecuteMsg::RebondPosition { token_id, id } => {
+            rebond_position(deps, env, info, token_id, Some(id))
+        }
+        ExecuteMsg::RebondAllPosition { token_id } => {
+            rebond_position(deps, env, info, token_id, None)
+        }
+        ExecuteMsg::ClaimPosition { token_id } => claim_position(deps, env, info, token_id),
+        ExecuteMsg::CompoundPosition { token_id } => compound_position(deps, env, info, token_id),
+        ExecuteMsg::SetPositionAutoCompound { token_id, enabled } => {
+            set_position_auto_compound(deps, env, info, token_id, enabled)
+        }
+        ExecuteMsg::TransferNft {
+            recipient,
+            token_id,
+        } => execute_position_nft(
+            deps,
+            env,
+            info,
+            Cw721ExecuteMsg::TransferNft {
+                recipient,
+                token_id,
+            },
+        ),
+        ExecuteMsg::SendNft {
+            contract,
+            token_id,
+            msg,
+        } => execute_position_nft(
+            deps,
+            env,
+            info,
+            Cw721ExecuteMsg::SendNft {
+                contract,
+                token_id,
+                msg,
+            },
+        ),
+        ExecuteMsg::Approve {
+            spender,
+            token_id,
+            expires,
+        } => execute_position_nft(
+            deps,
+            env,
+            info,
+            Cw721ExecuteMsg::Approve {
+                spender,
+                token_id,
+                expires,
+            },
+        ),
+        ExecuteMsg::Revoke { spender, token_id } => execute_position_nft(
+            deps,
+            env,
+            info,
+            Cw721ExecuteMsg::Revoke { spender, token_id },
+        ),
+        ExecuteMsg::ApproveAll { operator, expires } => execute_position_nft(
+            deps,
+            env,
+            info,
+            Cw721ExecuteMsg::ApproveAll { operator, expires },
+        ),
+        ExecuteMsg::RevokeAll { operator } => {
+            execute_position_nft(deps, env, info, Cw721ExecuteMsg::RevokeAll { operator })
+        }
+        ExecuteMsg::ReceiveNft(msg) => receive_nft(deps, env, info, msg),
+        ExecuteMsg::WithdrawNft {} => withdraw_nft(deps, env, info),
     }
 }
 
-pub fn stake(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response, ContractError> {
+pub fn stake_native(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    lock_tier: Option<usize>,
+) -> Result<Response, ContractError> {
     let config = CONFIG.load(deps.storage)?;
 
-    let mut staker_info: StakerInfo = user_staking(deps.as_ref(), info.sender.as_str())?;
-
-    let mut state: State = load_state(deps.as_ref())?;
-
     // Transfer tokens from sender to the contract
-    if info.funds.len() != 1 || info.funds[0].denom != config.kart_denom {
+    if config.kart_token_type != TokenType::Native
+        || info.funds.len() != 1
+        || info.funds[0].denom != config.kart_denom
+    {
         return Err(ContractError::UnsupportedToken {});
     }
 
-    let amount = info
-        .funds
-        .iter()
-        .find(|coin| coin.denom == config.kart_denom)
-        .map(|coin| coin.amount)
-        .unwrap_or(Uint128::zero());
+    stake(deps, env, info.sender, info.funds[0].amount, lock_tier)
+}
+
+pub fn receive_cw20(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    cw20_msg: Cw20ReceiveMsg,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let sender = deps.api.addr_validate(&cw20_msg.sender)?;
+
+    match from_json(&cw20_msg.msg)? {
+        Cw20HookMsg::Stake { lock_tier } => {
+            // only the configured KART token contract can stake
+            if config.kart_token_type != TokenType::Cw20 || info.sender != config.kart_denom {
+                return Err(ContractError::UnsupportedToken {});
+            }
+
+            stake(deps, env, sender, cw20_msg.amount, lock_tier)
+        }
+        Cw20HookMsg::SetDistribution {
+            start_date,
+            end_date,
+            curve,
+        } => {
+            assert_owner(deps.as_ref(), &sender)?;
+
+            add_distribution_schedule(
+                deps,
+                AssetInfo::Cw20 {
+                    contract_addr: info.sender,
+                },
+                start_date,
+                end_date,
+                cw20_msg.amount,
+                curve.unwrap_or(EmissionCurve::Linear),
+            )
+        }
+        Cw20HookMsg::TopUpSchedule { id } => {
+            assert_owner(deps.as_ref(), &sender)?;
+
+            fund_schedule(
+                deps,
+                env,
+                id,
+                AssetInfo::Cw20 {
+                    contract_addr: info.sender,
+                },
+                cw20_msg.amount,
+            )
+        }
+        Cw20HookMsg::DepositRevenue {} => {
+            let key = AssetInfo::Cw20 {
+                contract_addr: info.sender,
+            }
+            .to_string();
+
+            deposit_revenue(deps, env, sender, vec![(key, cw20_msg.amount)])
+        }
+    }
+}
+
+pub fn stake(
+    deps: DepsMut,
+    env: Env,
+    sender: Addr,
+    amount: Uint128,
+    lock_tier: Option<usize>,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
 
     if amount == Uint128::zero() {
         return Err(ContractError::InvalidAmount {});
     }
 
-    if staker_info.stake_amount == Uint128::zero() {
+    let lock_tier = lock_tier
+        .map(|i| {
+            config
+                .lock_tiers
+                .get(i)
+                .cloned()
+                .ok_or(ContractError::CustomError {
+                    msg: "invalid lock tier".to_string(),
+                })
+        })
+        .transpose()?;
+
+    if config.position_nft {
+        return stake_position(deps, env, sender, amount, lock_tier);
+    }
+
+    let mut staker_info: StakerInfo = user_staking(deps.as_ref(), sender.as_str())?;
+
+    let mut state: State = load_state(deps.as_ref())?;
+
+    if staker_info.share.is_zero() {
         state.total_staker += 1;
     }
 
-    compute_reward(&config, &mut state, env.block.time.seconds());
+    compute_reward(deps.storage, &mut state, env.block.time.seconds())?;
 
-    compute_staker_reward(&state, &mut staker_info)?;
+    settle_staker(
+        deps.storage,
+        &config,
+        &mut state,
+        Some(&sender),
+        &mut staker_info,
+    )?;
     // Increase bond_amount
-    increase_stake_amount(&mut state, &mut staker_info, amount);
+    let share = increase_stake_amount(&mut state, &mut staker_info, amount);
+    average_staked_at(&mut staker_info, share, env.block.time.seconds());
+    if let Some(lock_tier) = &lock_tier {
+        lock_share(
+            deps.storage,
+            &mut state,
+            &mut staker_info,
+            share,
+            lock_tier,
+            env.block.time.seconds(),
+        )?;
+    }
+    mint_receipt(deps.storage, &sender, share)?;
 
-    store_user_staking(deps.storage, info.sender.as_str(), &staker_info)?;
+    store_user_staking(
+        deps.storage,
+        sender.as_str(),
+        &staker_info,
+        env.block.height,
+    )?;
     store_state(deps.storage, &state)?;
 
     Ok(Response::new().add_attributes(vec![
         ("action", "stake"),
-        ("owner", info.sender.as_str()),
+        ("owner", sender.as_str()),
         ("amount", amount.to_string().as_str()),
     ]))
 }
@@ -130,27 +539,38 @@ pub fn unstake(
 
     let mut staker_info: StakerInfo = user_staking(deps.as_ref(), sender.as_str())?;
 
-    if staker_info.stake_amount < amount {
-        return Err(ContractError::InsufficientToken {});
-    }
-
     let mut state: State = load_state(deps.as_ref())?;
-    compute_reward(&config, &mut state, env.block.time.seconds());
+    compute_reward(deps.storage, &mut state, env.block.time.seconds())?;
+
+    settle_staker(
+        deps.storage,
+        &config,
+        &mut state,
+        Some(&sender),
+        &mut staker_info,
+    )?;
+
+    check_unstake(&state, &staker_info, amount)?;
 
-    compute_staker_reward(&state, &mut staker_info)?;
     // decrease bond_amount
-    decrease_stake_amount(
+    let share = decrease_stake_amount(
         &mut state,
         &mut staker_info,
         amount,
-        env.block.time.seconds() + config.unlock_time,
+        Some(env.block.time.seconds() + config.unlock_time),
     );
+    burn_receipt(deps.storage, &sender, share)?;
 
-    if staker_info.stake_amount == Uint128::zero() {
+    if staker_info.share.is_zero() {
         state.total_staker -= 1;
     }
 
-    store_user_staking(deps.storage, sender.as_str(), &staker_info)?;
+    store_user_staking(
+        deps.storage,
+        sender.as_str(),
+        &staker_info,
+        env.block.height,
+    )?;
     store_state(deps.storage, &state)?;
 
     Ok(Response::new().add_attributes(vec![
@@ -160,73 +580,39 @@ pub fn unstake(
     ]))
 }
 
+// withdraw matured unlocks, all of them when id is None
 pub fn withdraw(
     mut deps: DepsMut,
     env: Env,
     info: MessageInfo,
-    id: usize,
+    id: Option<u64>,
 ) -> Result<Response, ContractError> {
     let config = CONFIG.load(deps.storage)?;
     let mut staker_info: StakerInfo = user_staking(deps.as_ref(), info.sender.as_str())?;
 
-    let amount: Uint128;
-    let unlock_at: u64;
-
-    match &mut staker_info.unlock {
-        Some(unlock) => {
-            if id < unlock.len() {
-                amount = unlock
-                    .get(id)
-                    .unwrap_or(&Unlock {
-                        amount: Uint128::zero(),
-                        at: 0,
-                    })
-                    .amount;
-                unlock_at = unlock
-                    .get(id)
-                    .unwrap_or(&Unlock {
-                        amount: Uint128::zero(),
-                        at: 0,
-                    })
-                    .at;
-
-                if env.block.time.seconds() < unlock_at {
-                    return Err(ContractError::CustomError {
-                        msg: "withdraw after pending period".to_string(),
-                    });
-                }
-
-                unlock.remove(id);
+    // the withdrawn unlock leaves the unlock pool, persist its removal
+    let mut state: State = load_state(deps.as_ref())?;
+    let amount = take_unlock(&mut state, &mut staker_info, id, env.block.time.seconds())?;
+    store_state(deps.storage, &state)?;
+    store_user_staking(
+        deps.storage,
+        info.sender.as_str(),
+        &staker_info,
+        env.block.height,
+    )?;
 
-                if unlock.len() == 0 && staker_info.stake_amount == Uint128::zero() {
-                    claim_reward(deps.branch(), env.clone(), info.clone())?;
-                    remove_user_staking(deps.storage, info.sender.as_str())?;
-                }
+    let mut messages: Vec<CosmosMsg> = vec![];
 
-                if unlock.len() == 0 {
-                    staker_info.unlock = None;
-                }
-            } else {
-                return Err(ContractError::CustomError {
-                    msg: "invalid index".to_string(),
-                });
-            }
-        }
-        None => {
-            return Err(ContractError::CustomError {
-                msg: "dont have any pending unstake".to_string(),
-            })
-        }
+    // the claim pays the rewards and removes the record, while claims are
+    // paused the rewards stay pending and the record is kept
+    if staker_info.unlock.is_none() && staker_info.share.is_zero() && !claim_paused(deps.storage)? {
+        let res = claim_reward(deps.branch(), env, info.clone())?;
+        messages = res.messages.into_iter().map(|m| m.msg).collect();
     }
 
     Ok(Response::new()
-        .add_message(BankMsg::Send {
-            to_address: info.sender.to_string(),
-            amount: vec![Coin {
-                denom: config.kart_denom,
-                amount,
-            }],
-        })
+        .add_message(config.kart_asset().transfer_msg(&info.sender, amount)?)
+        .add_messages(messages)
         .add_attributes(vec![
             ("action", "withdraw"),
             ("owner", info.sender.to_string().as_str()),
@@ -234,160 +620,2229 @@ pub fn withdraw(
         ]))
 }
 
-// withdraw rewards to executor
-pub fn claim_reward(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response, ContractError> {
-    let config: Config = CONFIG.load(deps.storage)?;
+// unstake without the unlock period, the penalty is withheld from the amount
+pub fn instant_unstake(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
     let mut staker_info: StakerInfo = user_staking(deps.as_ref(), info.sender.as_str())?;
 
     let mut state: State = load_state(deps.as_ref())?;
+    compute_reward(deps.storage, &mut state, env.block.time.seconds())?;
+    settle_staker(
+        deps.storage,
+        &config,
+        &mut state,
+        Some(&info.sender),
+        &mut staker_info,
+    )?;
 
-    // Compute global reward & staker reward
-    compute_reward(&config, &mut state, env.block.time.seconds());
+    check_unstake(&state, &staker_info, amount)?;
 
-    compute_staker_reward(&state, &mut staker_info)?;
+    let share = decrease_stake_amount(&mut state, &mut staker_info, amount, None);
+    burn_receipt(deps.storage, &info.sender, share)?;
 
-    let mut reward: Vec<Coin> = vec![];
+    let (penalty, mut messages) = withhold_penalty(
+        deps.storage,
+        &config,
+        &mut state,
+        staker_info.staked_at,
+        amount,
+        env.block.time.seconds(),
+    )?;
+    messages.insert(
+        0,
+        config
+            .kart_asset()
+            .transfer_msg(&info.sender, amount - penalty)?,
+    );
 
-    for (denom, reward_amount) in staker_info.pending_reward {
-        staker_info
-            .reward_claimed
-            .entry(denom.clone())
-            .and_modify(|e| *e += reward_amount)
-            .or_insert(reward_amount);
-        reward.push(Coin::new(reward_amount.u128(), denom));
+    if staker_info.share.is_zero() {
+        state.total_staker -= 1;
     }
 
-    staker_info.pending_reward = HashMap::new();
-
-    store_user_staking(deps.storage, info.sender.as_str(), &staker_info)?;
-    store_state(deps.storage, &state)?;
-
-    // Store updated state
-
-    Ok(Response::new()
-        .add_message(BankMsg::Send {
-            to_address: info.sender.to_string(),
-            amount: reward,
-        })
-        .add_attributes(vec![
-            ("action", "claim_reward"),
-            ("owner", info.sender.as_str()),
-        ]))
-}
-
-pub fn unlock(
-    deps: DepsMut,
-    _env: Env,
-    info: MessageInfo,
-    amount: Uint128,
-    denom: String
-) -> Result<Response, ContractError> {
-    let config = CONFIG.load(deps.storage)?;
-
-    if config.owner != info.sender {
-        return Err(ContractError::Unauthorized {});
+    // a fully exited staker is paid its rewards and removed, unless claims are paused
+    if staker_info.share.is_zero() && staker_info.unlock.is_none() && !claim_paused(deps.storage)? {
+        messages.extend(take_pending_reward(
+            deps.storage,
+            &mut staker_info,
+            &info.sender,
+        )?);
+        if let Some(boost) = &staker_info.boost {
+            messages.push(boost.return_msg(&info.sender)?);
+        }
+        remove_user_staking(
+            deps.storage,
+            info.sender.as_str(),
+            &staker_info,
+            env.block.height,
+        )?;
+    } else {
+        store_user_staking(
+            deps.storage,
+            info.sender.as_str(),
+            &staker_info,
+            env.block.height,
+        )?;
     }
+    store_state(deps.storage, &state)?;
 
-    Ok(Response::new()
-        .add_message(BankMsg::Send {
-            to_address: info.sender.to_string(),
-            amount: vec![Coin {
-                denom,
-                amount,
-            }],
-        })
-        .add_attributes(vec![
-            ("action", "unlock"),
-            ("owner", info.sender.to_string().as_str()),
-            ("amount", amount.to_string().as_str()),
-        ]))
+    Ok(Response::new().add_messages(messages).add_attributes(vec![
+        ("action", "instant_unstake"),
+        ("owner", info.sender.as_str()),
+        ("amount", amount.to_string().as_str()),
+        ("penalty", penalty.to_string().as_str()),
+    ]))
 }
 
-pub fn update_config(
+// stake pending unlocks again, all of them when id is None
+pub fn rebond(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
-    new_config: Config,
+    id: Option<u64>,
 ) -> Result<Response, ContractError> {
     let config = CONFIG.load(deps.storage)?;
+    let mut staker_info: StakerInfo = user_staking(deps.as_ref(), info.sender.as_str())?;
 
-    if config.owner != info.sender {
-        return Err(ContractError::Unauthorized {});
-    }
-
-    CONFIG.save(deps.storage, &new_config)?;
-
-    Ok(Response::new().add_attributes(vec![("action", "update_config")]))
-}
-
-pub fn set_distribution_schedule(
-    deps: DepsMut,
-    _env: Env,
-    info: MessageInfo,
-    reward_denom: String,
-    start_date: u64,
-    end_date: u64,
-    amount: Uint128,
-) -> Result<Response, ContractError> {
-    let mut config = CONFIG.load(deps.storage)?;
     let mut state: State = load_state(deps.as_ref())?;
+    compute_reward(deps.storage, &mut state, env.block.time.seconds())?;
+    settle_staker(
+        deps.storage,
+        &config,
+        &mut state,
+        Some(&info.sender),
+        &mut staker_info,
+    )?;
 
-    if info.sender != config.owner {
-        return Err(ContractError::Unauthorized {});
+    let amount = take_rebond(&mut state, &mut staker_info, id)?;
+
+    // a fully exited staker comes back
+    if staker_info.share.is_zero() {
+        state.total_staker += 1;
     }
 
-    config
-        .distribution_schedule
-        .push((start_date, end_date, amount, reward_denom.clone()));
-    state
-        .reward_distributed
-        .entry(reward_denom)
-        .and_modify(|e| *e += amount)
-        .or_insert(amount);
+    let share = increase_stake_amount(&mut state, &mut staker_info, amount);
+    average_staked_at(&mut staker_info, share, env.block.time.seconds());
+    mint_receipt(deps.storage, &info.sender, share)?;
 
+    store_user_staking(
+        deps.storage,
+        info.sender.as_str(),
+        &staker_info,
+        env.block.height,
+    )?;
     store_state(deps.storage, &state)?;
 
-    CONFIG.save(deps.storage, &config)?;
-
-    Ok(Response::new().add_attributes(vec![("action", "update_config")]))
+    Ok(Response::new().add_attributes(vec![
+        ("action", "rebond"),
+        ("owner", info.sender.as_str()),
+        ("amount", amount.to_string().as_str()),
+    ]))
 }
 
-fn compute_reward(config: &Config, state: &mut State, block_time: u64) {
-    if state.total_stake_amount.is_zero() {
-        state.last_distributed = block_time;
-        return;
-    };
+// withdraw rewards to executor
+pub fn claim_reward(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response, ContractError> {
+    let config: Config = CONFIG.load(deps.storage)?;
+    let mut staker_info: StakerInfo = user_staking(deps.as_ref(), info.sender.as_str())?;
 
-    for s in config.distribution_schedule.iter() {
-        if s.0 > block_time || s.1 < state.last_distributed {
-            continue;
-        }
+    let mut state: State = load_state(deps.as_ref())?;
 
-        let passed_time =
-            std::cmp::min(s.1, block_time) - std::cmp::max(s.0, state.last_distributed);
+    // Compute global reward & staker reward
+    compute_reward(deps.storage, &mut state, env.block.time.seconds())?;
 
-        let time = s.1 - s.0;
-        let distribution_amount_per_second = Decimal::from_ratio(s.2, time);
+    settle_staker(
+        deps.storage,
+        &config,
+        &mut state,
+        Some(&info.sender),
+        &mut staker_info,
+    )?;
 
-        let reward_index_delta = Decimal::from_ratio(
-            distribution_amount_per_second * Uint128::from(passed_time as u128),
-            state.total_stake_amount,
-        );
+    let mut messages = take_pending_reward(deps.storage, &mut staker_info, &info.sender)?;
 
-        state
-            .reward_index
-            .entry(s.3.clone())
-            .and_modify(|e| *e += reward_index_delta)
-            .or_insert(reward_index_delta);
+    // a fully exited staker is removed once its rewards are paid,
+    // a deposited boost NFT goes back with the record
+    if staker_info.share.is_zero() && staker_info.unlock.is_none() {
+        if let Some(boost) = &staker_info.boost {
+            messages.push(boost.return_msg(&info.sender)?);
+        }
+        remove_user_staking(
+            deps.storage,
+            info.sender.as_str(),
+            &staker_info,
+            env.block.height,
+        )?;
+    } else {
+        store_user_staking(
+            deps.storage,
+            info.sender.as_str(),
+            &staker_info,
+            env.block.height,
+        )?;
     }
+    store_state(deps.storage, &state)?;
 
-    state.last_distributed = block_time;
+    Ok(Response::new().add_messages(messages).add_attributes(vec![
+        ("action", "claim_reward"),
+        ("owner", info.sender.as_str()),
+    ]))
+}
+
+pub fn compound(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response, ContractError> {
+    let config: Config = CONFIG.load(deps.storage)?;
+    let mut staker_info: StakerInfo = user_staking(deps.as_ref(), info.sender.as_str())?;
+
+    let mut state: State = load_state(deps.as_ref())?;
+    compute_reward(deps.storage, &mut state, env.block.time.seconds())?;
+    compute_staker_reward(
+        deps.storage,
+        &RewardIndexes::load(deps.storage)?,
+        &mut state,
+        &mut staker_info,
+    )?;
+
+    if staker_info.share.is_zero() {
+        state.total_staker += 1;
+    }
+
+    let (amount, share) = compound_reward(&config, &mut state, &mut staker_info);
+    if amount.is_zero() {
+        return Err(ContractError::InvalidAmount {});
+    }
+    record_reward_paid(deps.storage, &config.kart_asset().to_string(), amount)?;
+    mint_receipt(deps.storage, &info.sender, share)?;
+
+    store_user_staking(
+        deps.storage,
+        info.sender.as_str(),
+        &staker_info,
+        env.block.height,
+    )?;
+    store_state(deps.storage, &state)?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "compound"),
+        ("owner", info.sender.as_str()),
+        ("amount", amount.to_string().as_str()),
+    ]))
+}
+
+pub fn set_auto_compound(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    enabled: bool,
+) -> Result<Response, ContractError> {
+    let mut staker_info: StakerInfo = user_staking(deps.as_ref(), info.sender.as_str())?;
+
+    staker_info.auto_compound = enabled;
+
+    store_user_staking(
+        deps.storage,
+        info.sender.as_str(),
+        &staker_info,
+        env.block.height,
+    )?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "set_auto_compound"),
+        ("owner", info.sender.as_str()),
+        ("enabled", enabled.to_string().as_str()),
+    ]))
+}
+
+pub fn recover_funds(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    asset: AssetInfo,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    assert_owner(deps.as_ref(), &info.sender)?;
+
+    let config = CONFIG.load(deps.storage)?;
+    let state: State = load_state(deps.as_ref())?;
+    let asset = validate_asset(deps.api, asset)?;
+
+    // stake, pending unlocks and rewards still owed belong to the stakers
+    let key = asset.to_string();
+    let balance = reward_balance(deps.as_ref(), &env, &config, &state, &asset)?;
+    if amount > balance.saturating_sub(owed_reward(deps.storage, &key)?) {
+        return Err(ContractError::SurplusExceeded {});
+    }
+
+    Ok(Response::new()
+        .add_message(asset.transfer_msg(&info.sender, amount)?)
+        .add_attributes(vec![
+            ("action", "recover_funds"),
+            ("owner", info.sender.as_str()),
+            ("asset", key.as_str()),
+            ("amount", amount.to_string().as_str()),
+        ]))
+}
+
+pub fn reclaim_undistributed(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    denom: String,
+) -> Result<Response, ContractError> {
+    assert_owner(deps.as_ref(), &info.sender)?;
+
+    let mut state: State = load_state(deps.as_ref())?;
+    let amount = take_undistributed(deps.storage, &mut state, env.block.time.seconds(), &denom)?;
+    store_state(deps.storage, &state)?;
+
+    Ok(Response::new()
+        .add_message(AssetInfo::from_key(&denom).transfer_msg(&info.sender, amount)?)
+        .add_attributes(vec![
+            ("action", "reclaim_undistributed"),
+            ("owner", info.sender.as_str()),
+            ("asset", denom.as_str()),
+            ("amount", amount.to_string().as_str()),
+        ]))
+}
+
+pub fn reschedule_undistributed(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    denom: String,
+    start_date: u64,
+    end_date: u64,
+    curve: Option<EmissionCurve>,
+) -> Result<Response, ContractError> {
+    assert_owner(deps.as_ref(), &info.sender)?;
+
+    let mut state: State = load_state(deps.as_ref())?;
+    let amount = take_undistributed(deps.storage, &mut state, env.block.time.seconds(), &denom)?;
+    store_state(deps.storage, &state)?;
+
+    add_distribution_schedule(
+        deps,
+        AssetInfo::from_key(&denom),
+        start_date,
+        end_date,
+        amount,
+        curve.unwrap_or(EmissionCurve::Linear),
+    )
+}
+
+// accrue up to block_time and take the undistributed rewards, which are no
+// longer owed afterwards
+fn take_undistributed(
+    storage: &mut dyn Storage,
+    state: &mut State,
+    block_time: u64,
+    key: &str,
+) -> Result<Uint128, ContractError> {
+    compute_reward(storage, state, block_time)?;
+
+    let amount = reward_totals(storage, key)?.undistributed;
+    if amount.is_zero() {
+        return Err(ContractError::CustomError {
+            msg: "nothing undistributed".to_string(),
+        });
+    }
+
+    update_reward_totals(storage, key, |totals| {
+        totals.undistributed = Uint128::zero();
+        totals.distributed -= amount;
+    })?;
+
+    Ok(amount)
+}
+
+// contract balance of an asset, minus the staked principal for KART
+fn reward_balance(
+    deps: Deps,
+    env: &Env,
+    config: &Config,
+    state: &State,
+    asset: &AssetInfo,
+) -> StdResult<Uint128> {
+    let balance = match asset {
+        AssetInfo::Native { denom } => {
+            deps.querier
+                .query_balance(&env.contract.address, denom)?
+                .amount
+        }
+        AssetInfo::Cw20 { contract_addr } => {
+            deps.querier
+                .query_wasm_smart::<BalanceResponse>(
+                    contract_addr,
+                    &Cw20QueryMsg::Balance {
+                        address: env.contract.address.to_string(),
+                    },
+                )?
+                .balance
+        }
+    };
+
+    if *asset == config.kart_asset() {
+        return Ok(balance.saturating_sub(state.total_stake_amount + state.total_unlock));
+    }
+
+    Ok(balance)
+}
+
+fn owed_reward(storage: &dyn Storage, key: &str) -> StdResult<Uint128> {
+    let totals = reward_totals(storage, key)?;
+    Ok(totals.distributed.saturating_sub(totals.paid))
+}
+
+// checks shared by instantiate and update_config
+fn validate_config(api: &dyn Api, config: &Config) -> Result<(), ContractError> {
+    if config.kart_token_type == TokenType::Cw20 {
+        api.addr_validate(&config.kart_denom)?;
+    }
+
+    if config.drawdown_epoch == 0 {
+        return Err(ContractError::CustomError {
+            msg: "drawdown epoch must not be zero".to_string(),
+        });
+    }
+
+    if config.max_drawdown >= Decimal::one() {
+        return Err(ContractError::CustomError {
+            msg: "max drawdown must be less than 1".to_string(),
+        });
+    }
+
+    if config.instant_unstake_penalty > Decimal::one() {
+        return Err(ContractError::CustomError {
+            msg: "instant unstake penalty must not exceed 1".to_string(),
+        });
+    }
+
+    if let Some(treasury) = &config.penalty_treasury {
+        api.addr_validate(treasury.as_str())?;
+    }
+
+    for (collection, multiplier) in &config.boost_collections {
+        if *multiplier < Decimal::one() {
+            return Err(ContractError::CustomError {
+                msg: "boost multiplier must be at least 1".to_string(),
+            });
+        }
+        api.addr_validate(collection.as_str())?;
+    }
+
+    Ok(())
+}
+
+pub fn update_config(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    new_config: Config,
+) -> Result<Response, ContractError> {
+    assert_owner(deps.as_ref(), &info.sender)?;
+
+    // outstanding stake is held in the KART asset and the position mode it was made in
+    let config = CONFIG.load(deps.storage)?;
+    if new_config.kart_denom != config.kart_denom
+        || new_config.kart_token_type != config.kart_token_type
+        || new_config.position_nft != config.position_nft
+    {
+        return Err(ContractError::CustomError {
+            msg: "kart asset and position mode cannot be changed".to_string(),
+        });
+    }
+    validate_config(deps.api, &new_config)?;
+
+    CONFIG.save(deps.storage, &new_config)?;
+
+    Ok(Response::new().add_attributes(vec![("action", "update_config")]))
+}
+
+pub fn propose_new_owner(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    owner: String,
+    expiry: Option<Expiration>,
+) -> Result<Response, ContractError> {
+    assert_owner(deps.as_ref(), &info.sender)?;
+
+    let owner = deps.api.addr_validate(&owner)?;
+
+    if expiry.is_some_and(|expiry| expiry.is_expired(&env.block)) {
+        return Err(ContractError::CustomError {
+            msg: "expiry already passed".to_string(),
+        });
+    }
+
+    PENDING_OWNER.save(
+        deps.storage,
+        &PendingOwner {
+            owner: owner.clone(),
+            expiry,
+        },
+    )?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "propose_new_owner"),
+        ("owner", info.sender.as_str()),
+        ("pending_owner", owner.as_str()),
+    ]))
+}
+
+pub fn accept_ownership(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let pending_owner =
+        PENDING_OWNER
+            .may_load(deps.storage)?
+            .ok_or(ContractError::CustomError {
+                msg: "no ownership proposal".to_string(),
+            })?;
+
+    if pending_owner.owner != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    if pending_owner
+        .expiry
+        .is_some_and(|expiry| expiry.is_expired(&env.block))
+    {
+        return Err(ContractError::CustomError {
+            msg: "ownership proposal expired".to_string(),
+        });
+    }
+
+    let previous_owner = OWNER.get(deps.as_ref())?.unwrap_or(info.sender.clone());
+    OWNER.set(deps.branch(), Some(info.sender.clone()))?;
+    PENDING_OWNER.remove(deps.storage);
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "accept_ownership"),
+        ("previous_owner", previous_owner.as_str()),
+        ("owner", info.sender.as_str()),
+    ]))
+}
+
+pub fn cancel_ownership_proposal(
+    deps: DepsMut,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    assert_owner(deps.as_ref(), &info.sender)?;
+
+    if !PENDING_OWNER.exists(deps.storage) {
+        return Err(ContractError::CustomError {
+            msg: "no ownership proposal".to_string(),
+        });
+    }
+
+    PENDING_OWNER.remove(deps.storage);
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "cancel_ownership_proposal"),
+        ("owner", info.sender.as_str()),
+    ]))
+}
+
+pub fn set_pauser(
+    deps: DepsMut,
+    info: MessageInfo,
+    pauser: Option<String>,
+) -> Result<Response, ContractError> {
+    assert_owner(deps.as_ref(), &info.sender)?;
+
+    let pauser = pauser
+        .map(|pauser| deps.api.addr_validate(&pauser))
+        .transpose()?;
+    PAUSER.set(deps, pauser.clone())?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "set_pauser"),
+        (
+            "pauser",
+            pauser
+                .map(|pauser| pauser.to_string())
+                .unwrap_or_default()
+                .as_str(),
+        ),
+    ]))
+}
+
+// reward accrual is time based, paused actions just catch up once resumed
+pub fn set_paused(
+    deps: DepsMut,
+    info: MessageInfo,
+    paused: PauseFlags,
+) -> Result<Response, ContractError> {
+    if !PAUSER.is_admin(deps.as_ref(), &info.sender)? {
+        assert_owner(deps.as_ref(), &info.sender)?;
+    }
+
+    PAUSED.save(deps.storage, &paused)?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "set_paused"),
+        ("stake", paused.stake.to_string().as_str()),
+        ("unstake", paused.unstake.to_string().as_str()),
+        ("claim", paused.claim.to_string().as_str()),
+        ("withdraw", paused.withdraw.to_string().as_str()),
+        (
+            "set_distribution",
+            paused.set_distribution.to_string().as_str(),
+        ),
+    ]))
+}
+
+pub fn set_emergency_withdraw(
+    deps: DepsMut,
+    info: MessageInfo,
+    enabled: bool,
+) -> Result<Response, ContractError> {
+    assert_owner(deps.as_ref(), &info.sender)?;
+
+    EMERGENCY_WITHDRAW.save(deps.storage, &enabled)?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "set_emergency_withdraw"),
+        ("enabled", enabled.to_string().as_str()),
+    ]))
+}
+
+// skips all reward math, so it works even if settling a staker fails
+pub fn emergency_withdraw(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    if !EMERGENCY_WITHDRAW.load(deps.storage)? {
+        return Err(ContractError::CustomError {
+            msg: "emergency withdraw is not enabled".to_string(),
+        });
+    }
+
+    let config = CONFIG.load(deps.storage)?;
+    let mut staker_info: StakerInfo = user_staking(deps.as_ref(), info.sender.as_str())?;
+
+    let mut state: State = load_state(deps.as_ref())?;
+    burn_receipt(deps.storage, &info.sender, staker_info.share)?;
+    let (amount, mut messages) =
+        remove_staker(deps.storage, &mut state, &info.sender, &mut staker_info)?;
+
+    remove_user_staking(
+        deps.storage,
+        info.sender.as_str(),
+        &staker_info,
+        env.block.height,
+    )?;
+    store_state(deps.storage, &state)?;
+
+    if !amount.is_zero() {
+        messages.insert(0, config.kart_asset().transfer_msg(&info.sender, amount)?);
+    }
+
+    Ok(Response::new().add_messages(messages).add_attributes(vec![
+        ("action", "emergency_withdraw"),
+        ("owner", info.sender.as_str()),
+        ("amount", amount.to_string().as_str()),
+    ]))
+}
+
+pub fn emergency_withdraw_position(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+) -> Result<Response, ContractError> {
+    if !EMERGENCY_WITHDRAW.load(deps.storage)? {
+        return Err(ContractError::CustomError {
+            msg: "emergency withdraw is not enabled".to_string(),
+        });
+    }
+
+    let config = CONFIG.load(deps.storage)?;
+    let mut position = load_position(deps.as_ref(), &info.sender, &token_id)?;
+
+    let mut state: State = load_state(deps.as_ref())?;
+    let (amount, mut messages) =
+        remove_staker(deps.storage, &mut state, &info.sender, &mut position)?;
+
+    remove_position(
+        deps.storage,
+        &info.sender,
+        &token_id,
+        &position,
+        env.block.height,
+    )?;
+    position_nft().burn(deps.branch(), env, info.clone(), token_id.clone())?;
+    store_state(deps.storage, &state)?;
+
+    if !amount.is_zero() {
+        messages.insert(0, config.kart_asset().transfer_msg(&info.sender, amount)?);
+    }
+
+    Ok(Response::new().add_messages(messages).add_attributes(vec![
+        ("action", "emergency_withdraw"),
+        ("owner", info.sender.as_str()),
+        ("amount", amount.to_string().as_str()),
+        ("token_id", token_id.as_str()),
+    ]))
+}
+
+// take a staker out of the totals without settling, returns its stake plus unlocks
+// and the message returning a deposited boost NFT. Only plain arithmetic, so a
+// broken reward checkpoint cannot block the exit
+fn remove_staker(
+    storage: &mut dyn Storage,
+    state: &mut State,
+    recipient: &Addr,
+    staker_info: &mut StakerInfo,
+) -> StdResult<(Uint128, Vec<CosmosMsg>)> {
+    let stake_amount = share_to_amount(state, staker_info.share);
+
+    if !staker_info.share.is_zero() {
+        state.total_staker = state.total_staker.saturating_sub(1);
+    }
+    state.total_share = state.total_share.saturating_sub(staker_info.share);
+    state.total_stake_amount = state.total_stake_amount.saturating_sub(stake_amount);
+
+    // the bonus of an expired lock already left total_weight at its expiry
+    let mut weight = staker_info.weight;
+    for lock in std::mem::take(&mut staker_info.locks) {
+        let bonus = staker_info.lock_bonus(&lock);
+        if lock.until > state.last_distributed {
+            unschedule_lock_expiry(storage, lock.until, bonus)?;
+        } else {
+            weight = weight.saturating_sub(bonus);
+            staker_info.expired_locks.push(lock.until);
+        }
+    }
+    state.total_weight = state.total_weight.saturating_sub(weight);
+
+    // the stored pending rewards are forfeited, so they are no longer owed
+    for (key, amount) in &staker_info.pending_reward {
+        update_reward_totals(storage, key, |totals| {
+            totals.paid = totals.paid.saturating_add(*amount).min(totals.distributed)
+        })?;
+    }
+
+    let unlock_amount: Uint128 = staker_info
+        .unlock
+        .iter()
+        .flatten()
+        .map(|unlock| release_unlock(state, unlock.share))
+        .sum();
+
+    let mut messages: Vec<CosmosMsg> = vec![];
+    if let Some(boost) = &staker_info.boost {
+        messages.push(boost.return_msg(recipient)?);
+    }
+
+    Ok((stake_amount + unlock_amount, messages))
+}
+
+fn check_paused(deps: Deps, msg: &ExecuteMsg) -> Result<(), ContractError> {
+    let paused = PAUSED.load(deps.storage)?;
+
+    let is_paused = match msg {
+        ExecuteMsg::Receive(cw20_msg) => match from_json(&cw20_msg.msg) {
+            Ok(Cw20HookMsg::SetDistribution { .. }) | Ok(Cw20HookMsg::TopUpSchedule { .. }) => {
+                paused.set_distribution
+            }
+            Ok(Cw20HookMsg::DepositRevenue {}) => false,
+            _ => paused.stake,
+        },
+        ExecuteMsg::Stake { .. }
+        | ExecuteMsg::Rebond { .. }
+        | ExecuteMsg::RebondAll {}
+        | ExecuteMsg::RebondPosition { .. }
+        | ExecuteMsg::RebondAllPosition { .. } => paused.stake,
+        ExecuteMsg::Unstake { .. }
+        | ExecuteMsg::InstantUnstake { .. }
+        | ExecuteMsg::UnstakePosition { .. }
+        | ExecuteMsg::InstantUnstakePosition { .. } => paused.unstake,
+        ExecuteMsg::Claim {}
+        | ExecuteMsg::Compound {}
+        | ExecuteMsg::ClaimPosition { .. }
+        | ExecuteMsg::CompoundPosition { .. } => paused.claim,
+        ExecuteMsg::Withdraw { .. }
+        | ExecuteMsg::WithdrawAll {}
+        | ExecuteMsg::WithdrawPosition { .. }
+        | ExecuteMsg::WithdrawAllPosition { .. } => paused.withdraw,
+        ExecuteMsg::SetDistribution { .. }
+        | ExecuteMsg::ExtendSchedule { .. }
+        | ExecuteMsg::TopUpSchedule { .. }
+        | ExecuteMsg::CancelSchedule { .. }
+        | ExecuteMsg::RescheduleUndistributed { .. } => paused.set_distribution,
+        _ => false,
+    };
+
+    if is_paused {
+        return Err(ContractError::Paused {});
+    }
+
+    Ok(())
+}
+
+// rewards are neither paid nor compounded while claims are paused
+fn claim_paused(storage: &dyn Storage) -> StdResult<bool> {
+    Ok(PAUSED.load(storage)?.claim)
+}
+
+fn assert_owner(deps: Deps, sender: &Addr) -> Result<(), ContractError> {
+    if !OWNER.is_admin(deps, sender)? {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    Ok(())
+}
+
+pub fn set_distribution_schedule(
+    deps: DepsMut,
+    info: MessageInfo,
+    reward_asset: AssetInfo,
+    start_date: u64,
+    end_date: u64,
+    amount: Uint128,
+    curve: Option<EmissionCurve>,
+) -> Result<Response, ContractError> {
+    assert_owner(deps.as_ref(), &info.sender)?;
+
+    // the reward has to arrive with the schedule
+    let paid = match &reward_asset {
+        AssetInfo::Native { denom } => must_pay(&info, denom)?,
+        AssetInfo::Cw20 { .. } => {
+            return Err(ContractError::CustomError {
+                msg: "cw20 rewards are funded through Receive".to_string(),
+            })
+        }
+    };
+    if paid != amount {
+        return Err(ContractError::CustomError {
+            msg: "funds must match the reward amount".to_string(),
+        });
+    }
+
+    add_distribution_schedule(
+        deps,
+        reward_asset,
+        start_date,
+        end_date,
+        amount,
+        curve.unwrap_or(EmissionCurve::Linear),
+    )
+}
+
+fn add_distribution_schedule(
+    deps: DepsMut,
+    reward_asset: AssetInfo,
+    start_date: u64,
+    end_date: u64,
+    amount: Uint128,
+    curve: EmissionCurve,
+) -> Result<Response, ContractError> {
+    if start_date >= end_date {
+        return Err(ContractError::CustomError {
+            msg: "schedule must end after it starts".to_string(),
+        });
+    }
+
+    curve.validate()?;
+    let schedule = Schedule {
+        start_date,
+        end_date,
+        amount,
+        asset: validate_asset(deps.api, reward_asset)?,
+        curve,
+        rebased_at: start_date,
+    };
+    check_schedule_emits(&schedule)?;
+
+    let reward_asset = schedule.asset.clone();
+    update_reward_totals(deps.storage, &reward_asset.to_string(), |totals| {
+        totals.distributed += amount
+    })?;
+    let id = store_new_schedule(deps.storage, &schedule)?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "set_distribution"),
+        ("id", id.to_string().as_str()),
+        ("asset", reward_asset.to_string().as_str()),
+        ("amount", amount.to_string().as_str()),
+    ]))
+}
+
+pub fn extend_schedule(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    id: u64,
+    end_date: u64,
+) -> Result<Response, ContractError> {
+    assert_owner(deps.as_ref(), &info.sender)?;
+
+    let block_time = env.block.time.seconds();
+    let mut schedule = load_running_schedule(deps.storage, id, block_time)?;
+    if end_date <= schedule.end_date {
+        return Err(ContractError::CustomError {
+            msg: "schedule can only be extended".to_string(),
+        });
+    }
+
+    let mut state: State = load_state(deps.as_ref())?;
+    compute_reward(deps.storage, &mut state, block_time)?;
+    rebase_schedule(&mut schedule, block_time);
+    schedule.end_date = end_date;
+    check_schedule_emits(&schedule)?;
+
+    SCHEDULES.save(deps.storage, id, &schedule)?;
+    store_state(deps.storage, &state)?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "extend_schedule"),
+        ("id", id.to_string().as_str()),
+        ("end_date", end_date.to_string().as_str()),
+    ]))
+}
+
+pub fn top_up_schedule(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    id: u64,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    assert_owner(deps.as_ref(), &info.sender)?;
+
+    let paid = one_coin(&info)?;
+    if paid.amount != amount {
+        return Err(ContractError::CustomError {
+            msg: "funds must match the reward amount".to_string(),
+        });
+    }
+
+    fund_schedule(
+        deps,
+        env,
+        id,
+        AssetInfo::Native { denom: paid.denom },
+        amount,
+    )
+}
+
+fn fund_schedule(
+    deps: DepsMut,
+    env: Env,
+    id: u64,
+    asset: AssetInfo,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    let block_time = env.block.time.seconds();
+    let mut schedule = load_running_schedule(deps.storage, id, block_time)?;
+    if schedule.asset != asset {
+        return Err(ContractError::UnsupportedToken {});
+    }
+
+    let mut state: State = load_state(deps.as_ref())?;
+    compute_reward(deps.storage, &mut state, block_time)?;
+    rebase_schedule(&mut schedule, block_time);
+    schedule.amount += amount;
+    check_schedule_emits(&schedule)?;
+
+    update_reward_totals(deps.storage, &asset.to_string(), |totals| {
+        totals.distributed += amount
+    })?;
+
+    SCHEDULES.save(deps.storage, id, &schedule)?;
+    store_state(deps.storage, &state)?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "top_up_schedule"),
+        ("id", id.to_string().as_str()),
+        ("amount", amount.to_string().as_str()),
+    ]))
+}
+
+pub fn cancel_schedule(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    id: u64,
+) -> Result<Response, ContractError> {
+    assert_owner(deps.as_ref(), &info.sender)?;
+
+    let block_time = env.block.time.seconds();
+    let mut schedule = load_running_schedule(deps.storage, id, block_time)?;
+
+    // accrue the streamed part first, only the rest is refunded
+    let mut state: State = load_state(deps.as_ref())?;
+    compute_reward(deps.storage, &mut state, block_time)?;
+    rebase_schedule(&mut schedule, block_time);
+    let refund = schedule.amount;
+
+    update_reward_totals(deps.storage, &schedule.asset.to_string(), |totals| {
+        totals.distributed -= refund
+    })?;
+
+    SCHEDULES.remove(deps.storage, id);
+    store_state(deps.storage, &state)?;
+
+    let mut messages: Vec<CosmosMsg> = vec![];
+    if !refund.is_zero() {
+        messages.push(schedule.asset.transfer_msg(&info.sender, refund)?);
+    }
+
+    Ok(Response::new().add_messages(messages).add_attributes(vec![
+        ("action", "cancel_schedule"),
+        ("id", id.to_string().as_str()),
+        ("refund", refund.to_string().as_str()),
+    ]))
+}
+
+fn load_running_schedule(
+    storage: &dyn Storage,
+    id: u64,
+    block_time: u64,
+) -> Result<Schedule, ContractError> {
+    let schedule = SCHEDULES
+        .may_load(storage, id)?
+        .ok_or(ContractError::CustomError {
+            msg: "invalid schedule id".to_string(),
+        })?;
+
+    if schedule.end_date <= block_time {
+        return Err(ContractError::CustomError {
+            msg: "schedule already ended".to_string(),
+        });
+    }
+
+    Ok(schedule)
+}
+
+// drop the part streamed up to block_time, which compute_reward already accrued,
+// so the remainder can be changed without touching past accrual
+fn rebase_schedule(schedule: &mut Schedule, block_time: u64) {
+    if block_time <= schedule.rebased_at {
+        return;
+    }
+
+    schedule.amount -= schedule.emitted(schedule.rebased_at, block_time);
+    schedule.rebased_at = block_time;
+}
+
+// a curve that is flat for the rest of the schedule would strand its amount
+fn check_schedule_emits(schedule: &Schedule) -> Result<(), ContractError> {
+    let integral = |t: u64| schedule.curve.integral(t - schedule.start_date);
+
+    if integral(schedule.end_date) <= integral(schedule.rebased_at) {
+        return Err(ContractError::CustomError {
+            msg: "schedule emits nothing".to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+pub fn add_game(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    game: String,
+    max_exposure: Uint128,
+) -> Result<Response, ContractError> {
+    assert_owner(deps.as_ref(), &info.sender)?;
+
+    let game = deps.api.addr_validate(&game)?;
+
+    if GAMES.has(deps.storage, game.as_str()) {
+        return Err(ContractError::CustomError {
+            msg: "game already registered".to_string(),
+        });
+    }
+
+    GAMES.save(
+        deps.storage,
+        game.as_str(),
+        &GameInfo {
+            revenue: HashMap::new(),
+            max_exposure,
+            total_payout: Uint128::zero(),
+            epoch_start: 0,
+            epoch_payout: Uint128::zero(),
+        },
+    )?;
+
+    Ok(Response::new().add_attributes(vec![("action", "add_game"), ("game", game.as_str())]))
+}
+
+pub fn update_game(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    game: String,
+    max_exposure: Uint128,
+) -> Result<Response, ContractError> {
+    assert_owner(deps.as_ref(), &info.sender)?;
+
+    let mut game_info = GAMES.load(deps.storage, &game)?;
+    game_info.max_exposure = max_exposure;
+    GAMES.save(deps.storage, &game, &game_info)?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "update_game"),
+        ("game", game.as_str()),
+        ("max_exposure", max_exposure.to_string().as_str()),
+    ]))
+}
+
+pub fn remove_game(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    game: String,
+) -> Result<Response, ContractError> {
+    assert_owner(deps.as_ref(), &info.sender)?;
+
+    if !GAMES.has(deps.storage, &game) {
+        return Err(ContractError::CustomError {
+            msg: "game not registered".to_string(),
+        });
+    }
+
+    GAMES.remove(deps.storage, &game);
+
+    Ok(Response::new().add_attributes(vec![("action", "remove_game"), ("game", game.as_str())]))
+}
+
+// distribute house profit of a registered game to the current stakers, keyed by reward asset
+pub fn deposit_revenue(
+    deps: DepsMut,
+    env: Env,
+    game: Addr,
+    revenue: Vec<(String, Uint128)>,
+) -> Result<Response, ContractError> {
+    let mut game_info = GAMES
+        .may_load(deps.storage, game.as_str())?
+        .ok_or(ContractError::Unauthorized {})?;
+
+    if revenue.is_empty() || revenue.iter().any(|(_, amount)| amount.is_zero()) {
+        return Err(ContractError::InvalidAmount {});
+    }
+
+    let mut state: State = load_state(deps.as_ref())?;
+
+    if state.total_weight.is_zero() {
+        return Err(ContractError::CustomError {
+            msg: "no stakers to distribute revenue".to_string(),
+        });
+    }
+
+    // settle scheduled rewards before the index moves
+    compute_reward(deps.storage, &mut state, env.block.time.seconds())?;
+
+    for (key, amount) in revenue.iter() {
+        let reward_index_delta = Decimal::from_ratio(*amount, state.total_weight);
+
+        add_reward_index(deps.storage, key, reward_index_delta)?;
+        update_reward_totals(deps.storage, key, |totals| totals.distributed += *amount)?;
+        game_info
+            .revenue
+            .entry(key.clone())
+            .and_modify(|e| *e += *amount)
+            .or_insert(*amount);
+    }
+
+    GAMES.save(deps.storage, game.as_str(), &game_info)?;
+    store_state(deps.storage, &state)?;
+
+    let revenue = revenue
+        .iter()
+        .map(|(key, amount)| format!("{}{}", amount, key))
+        .collect::<Vec<String>>()
+        .join(",");
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "deposit_revenue"),
+        ("game", game.as_str()),
+        ("amount", revenue.as_str()),
+    ]))
+}
+
+// pay a game winner out of the bonded KART, the loss is shared by all stakers
+pub fn payout(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    recipient: String,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    let mut game_info = GAMES
+        .may_load(deps.storage, info.sender.as_str())?
+        .ok_or(ContractError::Unauthorized {})?;
+
+    let recipient = deps.api.addr_validate(&recipient)?;
+
+    if amount.is_zero() {
+        return Err(ContractError::InvalidAmount {});
+    }
+
+    let mut state: State = load_state(deps.as_ref())?;
+
+    // start a new epoch with a snapshot of the bankroll
+    if env.block.time.seconds() >= state.epoch_start + config.drawdown_epoch {
+        state.epoch_start = env.block.time.seconds();
+        state.epoch_bankroll = state.total_stake_amount + state.total_unlock;
+        state.epoch_drawdown = Uint128::zero();
+    }
+
+    // the game's exposure resets with the drawdown epoch
+    if game_info.epoch_start != state.epoch_start {
+        game_info.epoch_start = state.epoch_start;
+        game_info.epoch_payout = Uint128::zero();
+    }
+
+    if game_info.epoch_payout + amount > game_info.max_exposure {
+        return Err(ContractError::ExposureLimitExceeded {});
+    }
+
+    let bankroll = state.total_stake_amount + state.total_unlock;
+    if state.epoch_drawdown + amount > state.epoch_bankroll * config.max_drawdown
+        || amount >= bankroll
+    {
+        return Err(ContractError::DrawdownCapExceeded {});
+    }
+
+    // pending unlocks take their part of the loss, rounded up so unbonding never dodges it
+    let stake_loss = amount.multiply_ratio(state.total_stake_amount, bankroll);
+    let unlock_loss = amount - stake_loss;
+
+    // shares are untouched, so every share and unlock share is now worth less KART
+    state.total_stake_amount -= stake_loss;
+    state.total_unlock -= unlock_loss;
+    state.epoch_drawdown += amount;
+    game_info.total_payout += amount;
+    game_info.epoch_payout += amount;
+
+    GAMES.save(deps.storage, info.sender.as_str(), &game_info)?;
+    store_state(deps.storage, &state)?;
+
+    Ok(Response::new()
+        .add_message(config.kart_asset().transfer_msg(&recipient, amount)?)
+        .add_attributes(vec![
+            ("action", "payout"),
+            ("game", info.sender.as_str()),
+            ("recipient", recipient.as_str()),
+            ("amount", amount.to_string().as_str()),
+        ]))
+}
+
+pub fn transfer_receipt(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    recipient: String,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    let recipient_addr = deps.api.addr_validate(&recipient)?;
+    move_share(
+        deps.branch(),
+        env.clone(),
+        &info.sender,
+        &recipient_addr,
+        amount,
+    )?;
+
+    Ok(execute_transfer(deps, env, info, recipient, amount)?)
+}
+
+pub fn send_receipt(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    contract: String,
+    amount: Uint128,
+    msg: Binary,
+) -> Result<Response, ContractError> {
+    let contract_addr = deps.api.addr_validate(&contract)?;
+    move_share(
+        deps.branch(),
+        env.clone(),
+        &info.sender,
+        &contract_addr,
+        amount,
+    )?;
+
+    Ok(execute_send(deps, env, info, contract, amount, msg)?)
+}
+
+pub fn transfer_receipt_from(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    owner: String,
+    recipient: String,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    let owner_addr = deps.api.addr_validate(&owner)?;
+    let recipient_addr = deps.api.addr_validate(&recipient)?;
+    move_share(
+        deps.branch(),
+        env.clone(),
+        &owner_addr,
+        &recipient_addr,
+        amount,
+    )?;
+
+    Ok(execute_transfer_from(
+        deps, env, info, owner, recipient, amount,
+    )?)
+}
+
+pub fn send_receipt_from(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    owner: String,
+    contract: String,
+    amount: Uint128,
+    msg: Binary,
+) -> Result<Response, ContractError> {
+    let owner_addr = deps.api.addr_validate(&owner)?;
+    let contract_addr = deps.api.addr_validate(&contract)?;
+    move_share(
+        deps.branch(),
+        env.clone(),
+        &owner_addr,
+        &contract_addr,
+        amount,
+    )?;
+
+    Ok(execute_send_from(
+        deps, env, info, owner, contract, amount, msg,
+    )?)
+}
+
+// settle both reward checkpoints before the receipt share changes hands,
+// runs ahead of the cw20 balance move so locked share is rejected first
+fn move_share(
+    deps: DepsMut,
+    env: Env,
+    from: &Addr,
+    to: &Addr,
+    share: Uint128,
+) -> Result<(), ContractError> {
+    if from == to {
+        return Ok(());
+    }
+
+    let config = CONFIG.load(deps.storage)?;
+    let mut state: State = load_state(deps.as_ref())?;
+    let mut from_info: StakerInfo = user_staking(deps.as_ref(), from.as_str())?;
+    let mut to_info: StakerInfo = user_staking(deps.as_ref(), to.as_str())?;
+
+    compute_reward(deps.storage, &mut state, env.block.time.seconds())?;
+    settle_staker(
+        deps.storage,
+        &config,
+        &mut state,
+        Some(from),
+        &mut from_info,
+    )?;
+    settle_staker(deps.storage, &config, &mut state, Some(to), &mut to_info)?;
+
+    if from_info.share < share {
+        return Err(ContractError::InsufficientToken {});
+    }
+    if from_info.share - from_info.locked_share() < share {
+        return Err(ContractError::StakeLocked {});
+    }
+
+    if to_info.share.is_zero() {
+        state.total_staker += 1;
+    }
+
+    from_info.share -= share;
+    to_info.share += share;
+    average_staked_at(&mut to_info, share, from_info.staked_at);
+    from_info.stake_amount = share_to_amount(&state, from_info.share);
+    to_info.stake_amount = share_to_amount(&state, to_info.share);
+    update_weight(&mut state, &mut from_info);
+    update_weight(&mut state, &mut to_info);
+
+    if from_info.share.is_zero() {
+        state.total_staker -= 1;
+    }
+
+    store_user_staking(deps.storage, from.as_str(), &from_info, env.block.height)?;
+    store_user_staking(deps.storage, to.as_str(), &to_info, env.block.height)?;
+    store_state(deps.storage, &state)?;
+
+    Ok(())
+}
+
+pub fn receive_nft(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    cw721_msg: Cw721ReceiveMsg,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    // the boost would attach to the wallet record, which holds no stake in position mode
+    if config.position_nft {
+        return Err(ContractError::CustomError {
+            msg: "boost NFTs are not supported with position NFTs".to_string(),
+        });
+    }
+
+    // info.sender is the NFT collection
+    let multiplier = config
+        .boost_collections
+        .iter()
+        .find(|(collection, _)| *collection == info.sender)
+        .map(|(_, multiplier)| *multiplier)
+        .ok_or(ContractError::UnsupportedToken {})?;
+
+    let staker = deps.api.addr_validate(&cw721_msg.sender)?;
+    let mut staker_info: StakerInfo = user_staking(deps.as_ref(), staker.as_str())?;
+
+    if staker_info.boost.is_some() {
+        return Err(ContractError::CustomError {
+            msg: "boost NFT already deposited".to_string(),
+        });
+    }
+
+    let mut state: State = load_state(deps.as_ref())?;
+    compute_reward(deps.storage, &mut state, env.block.time.seconds())?;
+    settle_staker(
+        deps.storage,
+        &config,
+        &mut state,
+        Some(&staker),
+        &mut staker_info,
+    )?;
+
+    let boost = Boost {
+        collection: info.sender.clone(),
+        token_id: cw721_msg.token_id.clone(),
+        multiplier,
+    };
+    set_boost(deps.storage, &mut state, &mut staker_info, Some(boost))?;
+
+    store_user_staking(
+        deps.storage,
+        staker.as_str(),
+        &staker_info,
+        env.block.height,
+    )?;
+    store_state(deps.storage, &state)?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "deposit_nft"),
+        ("owner", staker.as_str()),
+        ("collection", info.sender.as_str()),
+        ("token_id", cw721_msg.token_id.as_str()),
+    ]))
+}
+
+pub fn withdraw_nft(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let mut staker_info: StakerInfo = user_staking(deps.as_ref(), info.sender.as_str())?;
+
+    let boost = staker_info
+        .boost
+        .clone()
+        .ok_or(ContractError::CustomError {
+            msg: "no boost NFT deposited".to_string(),
+        })?;
+
+    // settle the boosted rewards before the weight drops
+    let mut state: State = load_state(deps.as_ref())?;
+    compute_reward(deps.storage, &mut state, env.block.time.seconds())?;
+    settle_staker(
+        deps.storage,
+        &config,
+        &mut state,
+        Some(&info.sender),
+        &mut staker_info,
+    )?;
+
+    set_boost(deps.storage, &mut state, &mut staker_info, None)?;
+
+    store_user_staking(
+        deps.storage,
+        info.sender.as_str(),
+        &staker_info,
+        env.block.height,
+    )?;
+    store_state(deps.storage, &state)?;
+
+    Ok(Response::new()
+        .add_message(boost.return_msg(&info.sender)?)
+        .add_attributes(vec![
+            ("action", "withdraw_nft"),
+            ("owner", info.sender.as_str()),
+            ("collection", boost.collection.as_str()),
+            ("token_id", boost.token_id.as_str()),
+        ]))
+}
+
+fn stake_position(
+    mut deps: DepsMut,
+    env: Env,
+    owner: Addr,
+    amount: Uint128,
+    lock_tier: Option<LockTier>,
+) -> Result<Response, ContractError> {
+    let mut state: State = load_state(deps.as_ref())?;
+
+    let token_id = (POSITION_SEQ.may_load(deps.storage)?.unwrap_or_default() + 1).to_string();
+    let mut position = StakerInfo::default();
+
+    // a new position starts from the current reward index
+    compute_reward(deps.storage, &mut state, env.block.time.seconds())?;
+    compute_staker_reward(
+        deps.storage,
+        &RewardIndexes::load(deps.storage)?,
+        &mut state,
+        &mut position,
+    )?;
+    let share = increase_stake_amount(&mut state, &mut position, amount);
+    average_staked_at(&mut position, share, env.block.time.seconds());
+    if let Some(lock_tier) = &lock_tier {
+        lock_share(
+            deps.storage,
+            &mut state,
+            &mut position,
+            share,
+            lock_tier,
+            env.block.time.seconds(),
+        )?;
+    }
+    state.total_staker += 1;
+
+    position_nft().mint(
+        deps.branch(),
+        env.clone(),
+        MessageInfo {
+            sender: env.contract.address,
+            funds: vec![],
+        },
+        MintMsg {
+            token_id: token_id.clone(),
+            owner: owner.to_string(),
+            token_uri: None,
+            extension: None,
+        },
+    )?;
+
+    POSITION_SEQ.save(deps.storage, &token_id.parse::<u64>().unwrap())?;
+    store_position(deps.storage, &owner, &token_id, &position, env.block.height)?;
+    store_state(deps.storage, &state)?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "stake"),
+        ("owner", owner.as_str()),
+        ("amount", amount.to_string().as_str()),
+        ("token_id", token_id.as_str()),
+    ]))
+}
+
+pub fn unstake_position(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let mut position = load_position(deps.as_ref(), &info.sender, &token_id)?;
+
+    let mut state: State = load_state(deps.as_ref())?;
+    compute_reward(deps.storage, &mut state, env.block.time.seconds())?;
+    settle_staker(deps.storage, &config, &mut state, None, &mut position)?;
+
+    check_unstake(&state, &position, amount)?;
+
+    decrease_stake_amount(
+        &mut state,
+        &mut position,
+        amount,
+        Some(env.block.time.seconds() + config.unlock_time),
+    );
+
+    if position.share.is_zero() {
+        state.total_staker -= 1;
+    }
+
+    store_position(
+        deps.storage,
+        &info.sender,
+        &token_id,
+        &position,
+        env.block.height,
+    )?;
+    store_state(deps.storage, &state)?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "unstake"),
+        ("owner", info.sender.as_str()),
+        ("amount", amount.to_string().as_str()),
+        ("token_id", token_id.as_str()),
+    ]))
+}
+
+pub fn instant_unstake_position(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let mut position = load_position(deps.as_ref(), &info.sender, &token_id)?;
+
+    let mut state: State = load_state(deps.as_ref())?;
+    compute_reward(deps.storage, &mut state, env.block.time.seconds())?;
+    settle_staker(deps.storage, &config, &mut state, None, &mut position)?;
+
+    check_unstake(&state, &position, amount)?;
+
+    decrease_stake_amount(&mut state, &mut position, amount, None);
+
+    let (penalty, mut messages) = withhold_penalty(
+        deps.storage,
+        &config,
+        &mut state,
+        position.staked_at,
+        amount,
+        env.block.time.seconds(),
+    )?;
+    messages.insert(
+        0,
+        config
+            .kart_asset()
+            .transfer_msg(&info.sender, amount - penalty)?,
+    );
+
+    if position.share.is_zero() {
+        state.total_staker -= 1;
+    }
+
+    // an emptied position pays out its rewards and is burned, unless claims are paused
+    if position.share.is_zero() && position.unlock.is_none() && !claim_paused(deps.storage)? {
+        messages.extend(take_pending_reward(
+            deps.storage,
+            &mut position,
+            &info.sender,
+        )?);
+
+        remove_position(
+            deps.storage,
+            &info.sender,
+            &token_id,
+            &position,
+            env.block.height,
+        )?;
+        position_nft().burn(deps.branch(), env, info.clone(), token_id.clone())?;
+    } else {
+        store_position(
+            deps.storage,
+            &info.sender,
+            &token_id,
+            &position,
+            env.block.height,
+        )?;
+    }
+    store_state(deps.storage, &state)?;
+
+    Ok(Response::new().add_messages(messages).add_attributes(vec![
+        ("action", "instant_unstake"),
+        ("owner", info.sender.as_str()),
+        ("amount", amount.to_string().as_str()),
+        ("penalty", penalty.to_string().as_str()),
+        ("token_id", token_id.as_str()),
+    ]))
+}
+
+pub fn withdraw_position(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+    id: Option<u64>,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let mut position = load_position(deps.as_ref(), &info.sender, &token_id)?;
+
+    let mut state: State = load_state(deps.as_ref())?;
+    let amount = take_unlock(&mut state, &mut position, id, env.block.time.seconds())?;
+
+    let mut messages = vec![config.kart_asset().transfer_msg(&info.sender, amount)?];
+
+    // an emptied position pays out its rewards and is burned, unless claims are paused
+    if position.unlock.is_none() && position.share.is_zero() && !claim_paused(deps.storage)? {
+        compute_reward(deps.storage, &mut state, env.block.time.seconds())?;
+        compute_staker_reward(
+            deps.storage,
+            &RewardIndexes::load(deps.storage)?,
+            &mut state,
+            &mut position,
+        )?;
+        messages.extend(take_pending_reward(
+            deps.storage,
+            &mut position,
+            &info.sender,
+        )?);
+
+        remove_position(
+            deps.storage,
+            &info.sender,
+            &token_id,
+            &position,
+            env.block.height,
+        )?;
+        position_nft().burn(deps.branch(), env, info.clone(), token_id.clone())?;
+    } else {
+        store_position(
+            deps.storage,
+            &info.sender,
+            &token_id,
+            &position,
+            env.block.height,
+        )?;
+    }
+    store_state(deps.storage, &state)?;
+
+    Ok(Response::new().add_messages(messages).add_attributes(vec![
+        ("action", "withdraw"),
+        ("owner", info.sender.as_str()),
+        ("amount", amount.to_string().as_str()),
+        ("token_id", token_id.as_str()),
+    ]))
+}
+
+pub fn rebond_position(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+    id: Option<u64>,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let mut position = load_position(deps.as_ref(), &info.sender, &token_id)?;
+
+    let mut state: State = load_state(deps.as_ref())?;
+    compute_reward(deps.storage, &mut state, env.block.time.seconds())?;
+    settle_staker(deps.storage, &config, &mut state, None, &mut position)?;
+
+    let amount = take_rebond(&mut state, &mut position, id)?;
+
+    if position.share.is_zero() {
+        state.total_staker += 1;
+    }
+
+    let share = increase_stake_amount(&mut state, &mut position, amount);
+    average_staked_at(&mut position, share, env.block.time.seconds());
+
+    store_position(
+        deps.storage,
+        &info.sender,
+        &token_id,
+        &position,
+        env.block.height,
+    )?;
+    store_state(deps.storage, &state)?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "rebond"),
+        ("owner", info.sender.as_str()),
+        ("amount", amount.to_string().as_str()),
+        ("token_id", token_id.as_str()),
+    ]))
+}
+
+pub fn claim_position(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let mut position = load_position(deps.as_ref(), &info.sender, &token_id)?;
+
+    let mut state: State = load_state(deps.as_ref())?;
+    compute_reward(deps.storage, &mut state, env.block.time.seconds())?;
+    settle_staker(deps.storage, &config, &mut state, None, &mut position)?;
+
+    let messages = take_pending_reward(deps.storage, &mut position, &info.sender)?;
+
+    // a position emptied while claims were paused is burned once paid
+    if position.share.is_zero() && position.unlock.is_none() {
+        remove_position(
+            deps.storage,
+            &info.sender,
+            &token_id,
+            &position,
+            env.block.height,
+        )?;
+        position_nft().burn(deps.branch(), env, info.clone(), token_id.clone())?;
+    } else {
+        store_position(
+            deps.storage,
+            &info.sender,
+            &token_id,
+            &position,
+            env.block.height,
+        )?;
+    }
+    store_state(deps.storage, &state)?;
+
+    Ok(Response::new().add_messages(messages).add_attributes(vec![
+        ("action", "claim_reward"),
+        ("owner", info.sender.as_str()),
+        ("token_id", token_id.as_str()),
+    ]))
+}
+
+pub fn compound_position(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let mut position = load_position(deps.as_ref(), &info.sender, &token_id)?;
+
+    let mut state: State = load_state(deps.as_ref())?;
+    compute_reward(deps.storage, &mut state, env.block.time.seconds())?;
+    compute_staker_reward(
+        deps.storage,
+        &RewardIndexes::load(deps.storage)?,
+        &mut state,
+        &mut position,
+    )?;
+
+    if position.share.is_zero() {
+        state.total_staker += 1;
+    }
+
+    let (amount, _) = compound_reward(&config, &mut state, &mut position);
+    if amount.is_zero() {
+        return Err(ContractError::InvalidAmount {});
+    }
+    record_reward_paid(deps.storage, &config.kart_asset().to_string(), amount)?;
+
+    store_position(
+        deps.storage,
+        &info.sender,
+        &token_id,
+        &position,
+        env.block.height,
+    )?;
+    store_state(deps.storage, &state)?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "compound"),
+        ("owner", info.sender.as_str()),
+        ("amount", amount.to_string().as_str()),
+        ("token_id", token_id.as_str()),
+    ]))
+}
+
+pub fn set_position_auto_compound(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+    enabled: bool,
+) -> Result<Response, ContractError> {
+    let mut position = load_position(deps.as_ref(), &info.sender, &token_id)?;
+
+    position.auto_compound = enabled;
+
+    store_position(
+        deps.storage,
+        &info.sender,
+        &token_id,
+        &position,
+        env.block.height,
+    )?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "set_auto_compound"),
+        ("owner", info.sender.as_str()),
+        ("enabled", enabled.to_string().as_str()),
+        ("token_id", token_id.as_str()),
+    ]))
+}
+
+pub fn execute_position_nft(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: Cw721ExecuteMsg<Extension, Empty>,
+) -> Result<Response, ContractError> {
+    // positions are keyed by token id, so rewards and unlocks move with the NFT.
+    // Their shares move with it in the voting snapshots
+    let moved = match &msg {
+        Cw721ExecuteMsg::TransferNft {
+            recipient: new_owner,
+            token_id,
+        }
+        | Cw721ExecuteMsg::SendNft {
+            contract: new_owner,
+            token_id,
+            ..
+        } => Some((
+            position_nft().tokens.load(deps.storage, token_id)?.owner,
+            deps.api.addr_validate(new_owner)?,
+            position(deps.storage, token_id)?.share,
+        )),
+        _ => None,
+    };
+
+    let height = env.block.height;
+    let res = position_nft().execute(deps.branch(), env, info, msg)?;
+
+    if let Some((owner, new_owner, share)) = moved {
+        snapshot_stake(deps.storage, &owner, share, Uint128::zero(), height)?;
+        snapshot_stake(deps.storage, &new_owner, Uint128::zero(), share, height)?;
+    }
+
+    Ok(res)
+}
+
+// only the current NFT owner can manage a position
+fn load_position(deps: Deps, sender: &Addr, token_id: &str) -> Result<StakerInfo, ContractError> {
+    let token = position_nft().tokens.load(deps.storage, token_id)?;
+
+    if token.owner != *sender {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    Ok(position(deps.storage, token_id)?)
+}
+
+// remove matured unlocks for withdrawal, all of them when id is None
+fn take_unlock(
+    state: &mut State,
+    staker_info: &mut StakerInfo,
+    id: Option<u64>,
+    block_time: u64,
+) -> Result<Uint128, ContractError> {
+    let unlock = staker_info
+        .unlock
+        .as_mut()
+        .ok_or(ContractError::CustomError {
+            msg: "dont have any pending unstake".to_string(),
+        })?;
+
+    let amount = match id {
+        Some(id) => {
+            let index = unlock_index(unlock, id)?;
+            if block_time < unlock[index].at {
+                return Err(ContractError::CustomError {
+                    msg: "withdraw after pending period".to_string(),
+                });
+            }
+            release_unlock(state, unlock.remove(index).share)
+        }
+        None => {
+            let mut amount = Uint128::zero();
+            unlock.retain(|unlock| {
+                if unlock.at > block_time {
+                    return true;
+                }
+                amount += release_unlock(state, unlock.share);
+                false
+            });
+            if amount.is_zero() {
+                return Err(ContractError::CustomError {
+                    msg: "withdraw after pending period".to_string(),
+                });
+            }
+            amount
+        }
+    };
+
+    if unlock.is_empty() {
+        staker_info.unlock = None;
+    }
+
+    Ok(amount)
+}
+
+// remove unlocks to be staked again, they need not be matured
+fn take_rebond(
+    state: &mut State,
+    staker_info: &mut StakerInfo,
+    id: Option<u64>,
+) -> Result<Uint128, ContractError> {
+    let unlock = staker_info
+        .unlock
+        .as_mut()
+        .ok_or(ContractError::CustomError {
+            msg: "dont have any pending unstake".to_string(),
+        })?;
+
+    let amount = match id {
+        Some(id) => {
+            let index = unlock_index(unlock, id)?;
+            release_unlock(state, unlock.remove(index).share)
+        }
+        None => unlock
+            .drain(..)
+            .map(|unlock| release_unlock(state, unlock.share))
+            .sum(),
+    };
+
+    if unlock.is_empty() {
+        staker_info.unlock = None;
+    }
+
+    Ok(amount)
+}
+
+fn unlock_index(unlock: &[Unlock], id: u64) -> Result<usize, ContractError> {
+    unlock
+        .iter()
+        .position(|unlock| unlock.id == id)
+        .ok_or(ContractError::CustomError {
+            msg: "invalid unlock id".to_string(),
+        })
+}
+
+// move settled rewards into claimed and build the payout messages
+fn take_pending_reward(
+    storage: &mut dyn Storage,
+    staker_info: &mut StakerInfo,
+    recipient: &Addr,
+) -> StdResult<Vec<CosmosMsg>> {
+    let mut reward: Vec<Coin> = vec![];
+    let mut messages: Vec<CosmosMsg> = vec![];
+
+    for (key, reward_amount) in staker_info.pending_reward.drain() {
+        if reward_amount.is_zero() {
+            continue;
+        }
+
+        staker_info
+            .reward_claimed
+            .entry(key.clone())
+            .and_modify(|e| *e += reward_amount)
+            .or_insert(reward_amount);
+        record_reward_paid(storage, &key, reward_amount)?;
+
+        match AssetInfo::from_key(&key) {
+            AssetInfo::Native { denom } => reward.push(Coin::new(reward_amount.u128(), denom)),
+            asset => messages.push(asset.transfer_msg(recipient, reward_amount)?),
+        }
+    }
+
+    if !reward.is_empty() {
+        messages.insert(
+            0,
+            BankMsg::Send {
+                to_address: recipient.to_string(),
+                amount: reward,
+            }
+            .into(),
+        );
+    }
+
+    Ok(messages)
+}
+
+// what the schedules emitted between state.last_distributed and block_time, per asset
+#[derive(Default)]
+struct Accrual {
+    index: HashMap<String, Decimal>,
+    undistributed: HashMap<String, Uint128>,
+    // reward indexes at the lock expiries passed on the way
+    expiries: Vec<(u64, HashMap<String, Decimal>)>,
+}
+
+// accrue the schedules up to block_time without writing, for queries. Locks
+// expiring in between end their boost at their expiry, splitting the accrual
+fn accrue_reward(storage: &dyn Storage, state: &mut State, block_time: u64) -> StdResult<Accrual> {
+    let mut accrual = Accrual::default();
+
+    let expiries = if block_time > state.last_distributed {
+        LOCK_EXPIRIES
+            .range(
+                storage,
+                Some(Bound::exclusive(state.last_distributed)),
+                Some(Bound::inclusive(block_time)),
+                Order::Ascending,
+            )
+            .collect::<StdResult<Vec<_>>>()?
+    } else {
+        vec![]
+    };
+
+    if !expiries.is_empty() {
+        let stored = reward_indexes(storage)?;
+        for (until, expiry) in expiries {
+            accrue_schedules(storage, state, until, &mut accrual)?;
+
+            let mut index = stored.clone();
+            for (key, delta) in &accrual.index {
+                *index.entry(key.clone()).or_default() += *delta;
+            }
+            accrual.expiries.push((until, index));
+            state.total_weight -= expiry.weight;
+        }
+    }
+    accrue_schedules(storage, state, block_time, &mut accrual)?;
+
+    Ok(accrual)
+}
+
+fn accrue_schedules(
+    storage: &dyn Storage,
+    state: &mut State,
+    block_time: u64,
+    accrual: &mut Accrual,
+) -> StdResult<()> {
+    for item in SCHEDULES.range(storage, None, None, Order::Ascending) {
+        let (_, s) = item?;
+        let from = std::cmp::max(s.rebased_at, state.last_distributed);
+        let to = std::cmp::min(s.end_date, block_time);
+        if from >= to {
+            continue;
+        }
+
+        // nobody earns what is emitted while nothing is staked
+        let emitted = s.emitted(from, to);
+        if state.total_weight.is_zero() {
+            *accrual
+                .undistributed
+                .entry(s.asset.to_string())
+                .or_default() += emitted;
+            continue;
+        }
+
+        let reward_index_delta = Decimal::from_ratio(emitted, state.total_weight);
+        *accrual.index.entry(s.asset.to_string()).or_default() += reward_index_delta;
+    }
+
+    state.last_distributed = block_time;
+
+    Ok(())
+}
+
+// accrue the schedules up to block_time, only the assets that emitted are written.
+// The caller stores the state, which holds the new last_distributed
+fn compute_reward(storage: &mut dyn Storage, state: &mut State, block_time: u64) -> StdResult<()> {
+    let accrual = accrue_reward(storage, state, block_time)?;
+
+    for (key, delta) in accrual.index {
+        add_reward_index(storage, &key, delta)?;
+    }
+    for (key, amount) in accrual.undistributed {
+        update_reward_totals(storage, &key, |totals| totals.undistributed += amount)?;
+    }
+    // the expiry stays until its locks are released, without weight left to remove
+    for (until, index) in accrual.expiries {
+        for (key, index) in index {
+            EXPIRY_INDEX.save(storage, (until, &key), &index)?;
+        }
+        LOCK_EXPIRIES.update(storage, until, |expiry| -> StdResult<_> {
+            Ok(LockExpiry {
+                weight: Uint128::zero(),
+                ..expiry.unwrap_or_default()
+            })
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Reward indexes a staker is settled against. Expiry indexes not accrued in
+/// memory are read from `EXPIRY_INDEX`.
+#[derive(Default)]
+struct RewardIndexes {
+    current: HashMap<String, Decimal>,
+    expiries: HashMap<u64, HashMap<String, Decimal>>,
 }
 
-fn compute_staker_reward(state: &State, staker_info: &mut StakerInfo) -> StdResult<()> {
-    for (reward_denom, reward_index) in &state.reward_index {
-        let pending_rewards = (staker_info.stake_amount * *reward_index).checked_sub(
-            staker_info.stake_amount
+impl RewardIndexes {
+    fn load(storage: &dyn Storage) -> StdResult<Self> {
+        Ok(RewardIndexes {
+            current: reward_indexes(storage)?,
+            expiries: HashMap::new(),
+        })
+    }
+
+    fn at_expiry(&self, storage: &dyn Storage, until: u64) -> StdResult<HashMap<String, Decimal>> {
+        match self.expiries.get(&until) {
+            Some(index) => Ok(index.clone()),
+            None => EXPIRY_INDEX
+                .prefix(until)
+                .range(storage, None, None, Order::Ascending)
+                .collect(),
+        }
+    }
+}
+
+fn record_reward_paid(storage: &mut dyn Storage, key: &str, amount: Uint128) -> StdResult<()> {
+    update_reward_totals(storage, key, |totals| totals.paid += amount)
+}
+
+// settle pending rewards up to state.last_distributed and refresh the weight
+fn compute_staker_reward(
+    storage: &dyn Storage,
+    reward_indexes: &RewardIndexes,
+    state: &mut State,
+    staker_info: &mut StakerInfo,
+) -> StdResult<()> {
+    refresh_stake_amount(state, staker_info);
+
+    // an expired lock boosts up to its expiry, where its weight already left total_weight
+    let mut expired: Vec<u64> = staker_info
+        .locks
+        .iter()
+        .map(|lock| lock.until)
+        .filter(|until| *until <= state.last_distributed)
+        .collect();
+    expired.sort_unstable();
+    expired.dedup();
+    for until in expired {
+        settle_staker_index(staker_info, &reward_indexes.at_expiry(storage, until)?)?;
+
+        let bonus: Uint128 = staker_info
+            .locks
+            .iter()
+            .filter(|lock| lock.until == until)
+            .map(|lock| staker_info.lock_bonus(lock))
+            .sum();
+        for lock in staker_info.locks.iter().filter(|lock| lock.until == until) {
+            staker_info.expired_locks.push(lock.until);
+        }
+        staker_info.locks.retain(|lock| lock.until != until);
+        staker_info.weight -= bonus;
+    }
+
+    settle_staker_index(staker_info, &reward_indexes.current)?;
+    update_weight(state, staker_info);
+
+    Ok(())
+}
+
+// accrue pending rewards at the current weight up to the given indexes
+fn settle_staker_index(
+    staker_info: &mut StakerInfo,
+    reward_indexes: &HashMap<String, Decimal>,
+) -> StdResult<()> {
+    for (reward_denom, reward_index) in reward_indexes {
+        let pending_rewards = (staker_info.weight * *reward_index).checked_sub(
+            staker_info.weight
                 * *staker_info
                     .reward_index
                     .get(reward_denom)
@@ -407,39 +2862,417 @@ fn compute_staker_reward(state: &State, staker_info: &mut StakerInfo) -> StdResu
     Ok(())
 }
 
-fn increase_stake_amount(state: &mut State, staker_info: &mut StakerInfo, amount: Uint128) {
+// settle a staker and restake its KART rewards if it opted into auto-compound,
+// the receipt owner is None for positions since they hold no sKART
+fn settle_staker(
+    storage: &mut dyn Storage,
+    config: &Config,
+    state: &mut State,
+    owner: Option<&Addr>,
+    staker_info: &mut StakerInfo,
+) -> StdResult<()> {
+    compute_staker_reward(storage, &RewardIndexes::load(storage)?, state, staker_info)?;
+
+    // an exited staker gets its rewards paid out instead, and paused claims
+    // leave the rewards pending
+    if staker_info.auto_compound && !staker_info.share.is_zero() && !claim_paused(storage)? {
+        let (amount, share) = compound_reward(config, state, staker_info);
+        record_reward_paid(storage, &config.kart_asset().to_string(), amount)?;
+        if let Some(owner) = owner {
+            mint_receipt(storage, owner, share)?;
+        }
+    }
+
+    Ok(())
+}
+
+// move pending KART rewards into the stake, returns the amount and the share minted
+fn compound_reward(
+    config: &Config,
+    state: &mut State,
+    staker_info: &mut StakerInfo,
+) -> (Uint128, Uint128) {
+    let key = config.kart_asset().to_string();
+    let amount = staker_info.pending_reward.remove(&key).unwrap_or_default();
+    if amount.is_zero() {
+        return (amount, Uint128::zero());
+    }
+
+    staker_info
+        .reward_claimed
+        .entry(key)
+        .and_modify(|e| *e += amount)
+        .or_insert(amount);
+    let share = increase_stake_amount(state, staker_info, amount);
+
+    (amount, share)
+}
+
+fn share_to_amount(state: &State, share: Uint128) -> Uint128 {
+    if state.total_share.is_zero() {
+        return Uint128::zero();
+    }
+
+    share.multiply_ratio(state.total_stake_amount, state.total_share)
+}
+
+fn unlock_share_to_amount(state: &State, share: Uint128) -> Uint128 {
+    if state.total_unlock_share.is_zero() {
+        return Uint128::zero();
+    }
+
+    share.multiply_ratio(state.total_unlock, state.total_unlock_share)
+}
+
+// take an unlock share out of the unlock pool and return its KART
+fn release_unlock(state: &mut State, share: Uint128) -> Uint128 {
+    let amount = unlock_share_to_amount(state, share);
+    state.total_unlock = state.total_unlock.saturating_sub(amount);
+    state.total_unlock_share = state.total_unlock_share.saturating_sub(share);
+    amount
+}
+
+fn refresh_stake_amount(state: &State, staker_info: &mut StakerInfo) {
+    staker_info.stake_amount = share_to_amount(state, staker_info.share);
+    for unlock in staker_info.unlock.iter_mut().flatten() {
+        unlock.amount = unlock_share_to_amount(state, unlock.share);
+    }
+}
+
+// reward weight is the share scaled by the boost plus the lock bonuses, call after settling rewards
+fn update_weight(state: &mut State, staker_info: &mut StakerInfo) {
+    let lock_bonus: Uint128 = staker_info
+        .locks
+        .iter()
+        .map(|lock| staker_info.lock_bonus(lock))
+        .sum();
+    let weight = staker_info.share * staker_info.boost_multiplier() + lock_bonus;
+
+    state.total_weight = state.total_weight - staker_info.weight + weight;
+    staker_info.weight = weight;
+}
+
+fn lock_share(
+    storage: &mut dyn Storage,
+    state: &mut State,
+    staker_info: &mut StakerInfo,
+    share: Uint128,
+    lock_tier: &LockTier,
+    block_time: u64,
+) -> StdResult<()> {
+    let lock = StakeLock {
+        share,
+        multiplier: lock_tier.multiplier,
+        until: block_time + lock_tier.duration,
+    };
+    // a lock already expired never boosts
+    if lock.until <= state.last_distributed {
+        return Ok(());
+    }
+    schedule_lock_expiry(storage, lock.until, staker_info.lock_bonus(&lock))?;
+    staker_info.locks.push(lock);
+    update_weight(state, staker_info);
+    Ok(())
+}
+
+// the boost scales the lock bonuses too, so their scheduled expiries move with it.
+// Call after settling, when every remaining lock is still scheduled
+fn set_boost(
+    storage: &mut dyn Storage,
+    state: &mut State,
+    staker_info: &mut StakerInfo,
+    boost: Option<Boost>,
+) -> StdResult<()> {
+    for lock in &staker_info.locks {
+        unschedule_lock_expiry(storage, lock.until, staker_info.lock_bonus(lock))?;
+    }
+    staker_info.boost = boost;
+    for lock in &staker_info.locks {
+        schedule_lock_expiry(storage, lock.until, staker_info.lock_bonus(lock))?;
+    }
+    update_weight(state, staker_info);
+    Ok(())
+}
+
+// every lock is counted, so the expiry index outlives none of them
+fn schedule_lock_expiry(storage: &mut dyn Storage, until: u64, bonus: Uint128) -> StdResult<()> {
+    LOCK_EXPIRIES.update(storage, until, |expiry| -> StdResult<_> {
+        let expiry = expiry.unwrap_or_default();
+        Ok(LockExpiry {
+            weight: expiry.weight + bonus,
+            locks: expiry.locks + 1,
+        })
+    })?;
+    Ok(())
+}
+
+// take back a lock that has not expired yet
+fn unschedule_lock_expiry(storage: &mut dyn Storage, until: u64, bonus: Uint128) -> StdResult<()> {
+    let expiry = LOCK_EXPIRIES.may_load(storage, until)?.unwrap_or_default();
+    let expiry = LockExpiry {
+        weight: expiry.weight.saturating_sub(bonus),
+        locks: expiry.locks.saturating_sub(1),
+    };
+    if expiry.locks == 0 {
+        LOCK_EXPIRIES.remove(storage, until);
+    } else {
+        LOCK_EXPIRIES.save(storage, until, &expiry)?;
+    }
+    Ok(())
+}
+
+// only the share outside of locks can be unstaked
+fn check_unstake(
+    state: &State,
+    staker_info: &StakerInfo,
+    amount: Uint128,
+) -> Result<(), ContractError> {
+    if staker_info.stake_amount < amount {
+        return Err(ContractError::InsufficientToken {});
+    }
+
+    let free_share = staker_info.share - staker_info.locked_share();
+    if share_to_amount(state, free_share) < amount {
+        return Err(ContractError::StakeLocked {});
+    }
+
+    Ok(())
+}
+
+fn increase_stake_amount(
+    state: &mut State,
+    staker_info: &mut StakerInfo,
+    amount: Uint128,
+) -> Uint128 {
+    let share = if state.total_share.is_zero() {
+        amount
+    } else {
+        amount.multiply_ratio(state.total_share, state.total_stake_amount)
+    };
+
+    state.total_share += share;
     state.total_stake_amount += amount;
-    staker_info.stake_amount += amount;
+    staker_info.share += share;
+    staker_info.stake_amount = share_to_amount(state, staker_info.share);
+    update_weight(state, staker_info);
+
+    share
 }
 
 fn decrease_stake_amount(
     state: &mut State,
     staker_info: &mut StakerInfo,
     amount: Uint128,
-    unlock_at: u64,
-) {
+    unlock_at: Option<u64>,
+) -> Uint128 {
+    // round the burned share up so the pool never pays out more than it holds
+    let mut share = amount.multiply_ratio(state.total_share, state.total_stake_amount);
+    if share.multiply_ratio(state.total_stake_amount, state.total_share) < amount {
+        share += Uint128::one();
+    }
+    let free_share = staker_info.share - staker_info.locked_share();
+    if amount == share_to_amount(state, free_share) || share > free_share {
+        share = free_share;
+    }
+
+    state.total_share -= share;
     state.total_stake_amount -= amount;
-    staker_info.stake_amount -= amount;
+    staker_info.share -= share;
+    staker_info.stake_amount = share_to_amount(state, staker_info.share);
+    update_weight(state, staker_info);
+
+    // the unlock moves into the unlock pool, which still takes its part of payouts
+    if let Some(unlock_at) = unlock_at {
+        let unlock_share = if state.total_unlock_share.is_zero() || state.total_unlock.is_zero() {
+            amount
+        } else {
+            amount.multiply_ratio(state.total_unlock_share, state.total_unlock)
+        };
+        let unlock = Unlock {
+            id: state.next_unlock_id,
+            amount,
+            share: unlock_share,
+            at: unlock_at,
+        };
+        state.next_unlock_id += 1;
+        state.total_unlock += amount;
+        state.total_unlock_share += unlock_share;
+
+        match &mut staker_info.unlock {
+            Some(unlocks) => unlocks.push(unlock),
+            None => staker_info.unlock = Some(vec![unlock]),
+        }
+    }
 
-    let unlock = Unlock {
-        amount: amount,
-        at: unlock_at,
-    };
+    share
+}
+
+// share weighted average of the stake times, call after share was added to the staker
+fn average_staked_at(staker_info: &mut StakerInfo, share: Uint128, staked_at: u64) {
+    if staker_info.share.is_zero() {
+        return;
+    }
+
+    let delta = Uint128::from(staked_at.abs_diff(staker_info.staked_at))
+        .multiply_ratio(share, staker_info.share)
+        .u128() as u64;
+    if staked_at > staker_info.staked_at {
+        staker_info.staked_at += delta;
+    } else {
+        staker_info.staked_at -= delta;
+    }
+}
+
+// withhold the instant unstake penalty, returns it and the treasury message if any
+fn withhold_penalty(
+    storage: &mut dyn Storage,
+    config: &Config,
+    state: &mut State,
+    staked_at: u64,
+    amount: Uint128,
+    block_time: u64,
+) -> StdResult<(Uint128, Vec<CosmosMsg>)> {
+    let mut rate = config.instant_unstake_penalty;
+    if config.penalty_decay > 0 {
+        let staked = std::cmp::min(block_time.saturating_sub(staked_at), config.penalty_decay);
+        rate *= Decimal::from_ratio(config.penalty_decay - staked, config.penalty_decay);
+    }
 
-    match &mut staker_info.unlock {
-        Some(unlocks) => unlocks.push(unlock),
-        None => staker_info.unlock = Some(vec![unlock]),
+    let penalty = amount * rate;
+    if penalty.is_zero() {
+        return Ok((penalty, vec![]));
+    }
+
+    if let Some(treasury) = &config.penalty_treasury {
+        return Ok((
+            penalty,
+            vec![config.kart_asset().transfer_msg(treasury, penalty)?],
+        ));
+    }
+
+    if state.total_weight.is_zero() {
+        // nobody is left to share it, the penalty stays in the bankroll
+        state.total_stake_amount += penalty;
+    } else {
+        let key = config.kart_asset().to_string();
+        let reward_index_delta = Decimal::from_ratio(penalty, state.total_weight);
+        add_reward_index(storage, &key, reward_index_delta)?;
+        update_reward_totals(storage, &key, |totals| totals.distributed += penalty)?;
+    }
+
+    Ok((penalty, vec![]))
+}
+
+fn validate_asset(api: &dyn Api, asset: AssetInfo) -> StdResult<AssetInfo> {
+    match asset {
+        AssetInfo::Cw20 { contract_addr } => Ok(AssetInfo::Cw20 {
+            contract_addr: api.addr_validate(contract_addr.as_str())?,
+        }),
+        asset => Ok(asset),
     }
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
         QueryMsg::Config {} => to_json_binary(&query_config(deps)?),
+        QueryMsg::Ownership {} => to_json_binary(&query_ownership(deps)?),
+        QueryMsg::RewardFunding {} => to_json_binary(&query_reward_funding(deps, env)?),
+        QueryMsg::PauseStatus {} => to_json_binary(&query_pause_status(deps)?),
         QueryMsg::State { block_time } => to_json_binary(&query_state(deps, block_time)?),
         QueryMsg::StakerInfo { staker, block_time } => {
             to_json_binary(&query_staker_info(deps, staker, block_time)?)
         }
+        QueryMsg::AllStakers {
+            start_after,
+            limit,
+            block_time,
+        } => to_json_binary(&query_all_stakers(deps, start_after, limit, block_time)?),
+        QueryMsg::TopStakers { limit, start_after } => {
+            to_json_binary(&query_top_stakers(deps, limit, start_after)?)
+        }
+        QueryMsg::StakedBalanceAtHeight { address, height } => {
+            to_json_binary(&query_staked_balance_at_height(deps, env, address, height)?)
+        }
+        QueryMsg::TotalStakedAtHeight { height } => {
+            to_json_binary(&query_total_staked_at_height(deps, env, height)?)
+        }
+        QueryMsg::Game { game } => to_json_binary(&query_game(deps, game)?),
+        QueryMsg::Schedule { id } => to_json_binary(&query_schedule(deps, id)?),
+        QueryMsg::Schedules {} => to_json_binary(&query_schedules(deps)?),
+        QueryMsg::Games {} => to_json_binary(&query_games(deps)?),
+        QueryMsg::Balance { address } => to_json_binary(&query_balance(deps, address)?),
+        QueryMsg::TokenInfo {} => to_json_binary(&query_token_info(deps)?),
+        QueryMsg::Allowance { owner, spender } => {
+            to_json_binary(&query_allowance(deps, owner, spender)?)
+        }
+        QueryMsg::Position {
+            token_id,
+            block_time,
+        } => to_json_binary(&query_position(deps, token_id, block_time)?),
+        QueryMsg::Positions {
+            start_after,
+            limit,
+            block_time,
+        } => to_json_binary(&query_positions(deps, start_after, limit, block_time)?),
+        QueryMsg::OwnerOf {
+            token_id,
+            include_expired,
+        } => position_nft().query(
+            deps,
+            env,
+            Cw721QueryMsg::OwnerOf {
+                token_id,
+                include_expired,
+            },
+        ),
+        QueryMsg::Approval {
+            token_id,
+            spender,
+            include_expired,
+        } => position_nft().query(
+            deps,
+            env,
+            Cw721QueryMsg::Approval {
+                token_id,
+                spender,
+                include_expired,
+            },
+        ),
+        QueryMsg::NumTokens {} => position_nft().query(deps, env, Cw721QueryMsg::NumTokens {}),
+        QueryMsg::ContractInfo {} => {
+            position_nft().query(deps, env, Cw721QueryMsg::ContractInfo {})
+        }
+        QueryMsg::NftInfo { token_id } => {
+            position_nft().query(deps, env, Cw721QueryMsg::NftInfo { token_id })
+        }
+        QueryMsg::AllNftInfo {
+            token_id,
+            include_expired,
+        } => position_nft().query(
+            deps,
+            env,
+            Cw721QueryMsg::AllNftInfo {
+                token_id,
+                include_expired,
+            },
+        ),
+        QueryMsg::Tokens {
+            owner,
+            start_after,
+            limit,
+        } => position_nft().query(
+            deps,
+            env,
+            Cw721QueryMsg::Tokens {
+                owner,
+                start_after,
+                limit,
+            },
+        ),
+        QueryMsg::AllTokens { start_after, limit } => {
+            position_nft().query(deps, env, Cw721QueryMsg::AllTokens { start_after, limit })
+        }
     }
 }
 
@@ -447,29 +3280,115 @@ pub fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
     let config = CONFIG.load(deps.storage)?;
 
     let resp = ConfigResponse {
-        owner: config.owner.to_string(),
+        owner: OWNER
+            .get(deps)?
+            .map(|owner| owner.to_string())
+            .unwrap_or_default(),
         kart_denom: config.kart_denom,
+        kart_token_type: config.kart_token_type,
         unlock_time: config.unlock_time,
-        distribution_schedule: config.distribution_schedule,
+        drawdown_epoch: config.drawdown_epoch,
+        max_drawdown: config.max_drawdown,
+        position_nft: config.position_nft,
+        boost_collections: config
+            .boost_collections
+            .into_iter()
+            .map(|(collection, multiplier)| (collection.to_string(), multiplier))
+            .collect(),
+        lock_tiers: config.lock_tiers,
+        instant_unstake_penalty: config.instant_unstake_penalty,
+        penalty_treasury: config.penalty_treasury.map(|treasury| treasury.to_string()),
+        penalty_decay: config.penalty_decay,
     };
 
     Ok(resp)
 }
 
+pub fn query_ownership(deps: Deps) -> StdResult<OwnershipResponse> {
+    let pending_owner = PENDING_OWNER.may_load(deps.storage)?;
+
+    Ok(OwnershipResponse {
+        owner: OWNER
+            .get(deps)?
+            .map(|owner| owner.to_string())
+            .unwrap_or_default(),
+        pending_owner: pending_owner
+            .as_ref()
+            .map(|pending_owner| pending_owner.owner.to_string()),
+        pending_expiry: pending_owner.and_then(|pending_owner| pending_owner.expiry),
+    })
+}
+
+pub fn query_reward_funding(deps: Deps, env: Env) -> StdResult<RewardFundingResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let state: State = load_state(deps)?;
+
+    let funding = all_reward_totals(deps.storage)?
+        .into_iter()
+        .filter(|(_, totals)| !totals.distributed.is_zero())
+        .map(|(key, totals)| {
+            let asset = AssetInfo::from_key(&key);
+            let owed = totals.distributed.saturating_sub(totals.paid);
+            let balance = reward_balance(deps, &env, &config, &state, &asset)?;
+            Ok(RewardFunding {
+                asset,
+                owed,
+                balance,
+                shortfall: owed.saturating_sub(balance),
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(RewardFundingResponse { funding })
+}
+
+pub fn query_pause_status(deps: Deps) -> StdResult<PauseStatusResponse> {
+    Ok(PauseStatusResponse {
+        pauser: PAUSER.get(deps)?.map(|pauser| pauser.to_string()),
+        paused: PAUSED.load(deps.storage)?,
+        emergency_withdraw: EMERGENCY_WITHDRAW.load(deps.storage)?,
+    })
+}
+
 pub fn query_state(deps: Deps, block_time: Option<u64>) -> StdResult<StateResponse> {
     let mut state: State = load_state(deps)?;
 
+    let mut reward_distributed: HashMap<String, Uint128> = HashMap::new();
+    let mut reward_paid: HashMap<String, Uint128> = HashMap::new();
+    let mut undistributed: HashMap<String, Uint128> = HashMap::new();
+    for (key, totals) in all_reward_totals(deps.storage)? {
+        insert_nonzero(&mut reward_distributed, &key, totals.distributed);
+        insert_nonzero(&mut reward_paid, &key, totals.paid);
+        insert_nonzero(&mut undistributed, &key, totals.undistributed);
+    }
+
+    let mut reward_index = reward_indexes(deps.storage)?;
     if let Some(block_time) = block_time {
-        let config = CONFIG.load(deps.storage)?;
-        compute_reward(&config, &mut state, block_time);
+        let accrual = accrue_reward(deps.storage, &mut state, block_time)?;
+        for (key, delta) in accrual.index {
+            *reward_index.entry(key).or_default() += delta;
+        }
+        for (key, amount) in accrual.undistributed {
+            *undistributed.entry(key).or_default() += amount;
+        }
     }
 
     Ok(StateResponse {
         total_staker: state.total_staker,
         total_stake_amount: state.total_stake_amount,
+        total_share: state.total_share,
+        total_weight: state.total_weight,
         last_distributed: state.last_distributed,
-        reward_distributed: state.reward_distributed,
-        reward_index: state.reward_index,
+        reward_distributed,
+        reward_index,
+        epoch_start: state.epoch_start,
+        epoch_bankroll: state.epoch_bankroll,
+        epoch_drawdown: state.epoch_drawdown,
+        next_unlock_id: state.next_unlock_id,
+        total_unlock: state.total_unlock,
+        total_unlock_share: state.total_unlock_share,
+        reward_paid,
+        undistributed,
     })
 }
 
@@ -480,21 +3399,284 @@ pub fn query_staker_info(
 ) -> StdResult<StakerInfoResponse> {
     let staker = deps.api.addr_validate(&staker)?;
 
-    let mut staker_info: StakerInfo = user_staking(deps, staker.as_str())?;
+    let staker_info: StakerInfo = user_staking(deps, staker.as_str())?;
+
+    staker_info_response(deps, staker_info, block_time)
+}
+
+pub fn query_position(
+    deps: Deps,
+    token_id: String,
+    block_time: Option<u64>,
+) -> StdResult<StakerInfoResponse> {
+    let position = position(deps.storage, &token_id)?;
+
+    staker_info_response(deps, position, block_time)
+}
+
+pub fn query_positions(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+    block_time: Option<u64>,
+) -> StdResult<PositionsResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let mut state: State = load_state(deps)?;
+    let reward_indexes = accrued_reward_indexes(deps, &mut state, block_time)?;
+
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.as_deref().map(Bound::exclusive);
+
+    let positions = POSITIONS
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|token_id| {
+            let token_id = token_id?;
+            let owner = position_nft().tokens.load(deps.storage, &token_id)?.owner;
+            let position = position(deps.storage, &token_id)?;
+            let info = settled_staker_info(
+                deps.storage,
+                &config,
+                &mut state,
+                &reward_indexes,
+                position,
+                block_time.is_some(),
+            )?;
+            Ok(PositionResponse {
+                token_id,
+                owner: owner.to_string(),
+                info,
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(PositionsResponse { positions })
+}
+
+pub fn query_all_stakers(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+    block_time: Option<u64>,
+) -> StdResult<AllStakersResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let mut state: State = load_state(deps)?;
+    let reward_indexes = accrued_reward_indexes(deps, &mut state, block_time)?;
+
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.as_deref().map(Bound::exclusive);
+
+    let stakers = USER_STAKING
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|staker| {
+            let staker = staker?;
+            let staker_info = user_staking(deps, &staker)?;
+            let info = settled_staker_info(
+                deps.storage,
+                &config,
+                &mut state,
+                &reward_indexes,
+                staker_info,
+                block_time.is_some(),
+            )?;
+            Ok(StakerResponse { staker, info })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(AllStakersResponse { stakers })
+}
+
+pub fn query_top_stakers(
+    deps: Deps,
+    limit: Option<u32>,
+    start_after: Option<String>,
+) -> StdResult<TopStakersResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let mut state: State = load_state(deps)?;
+
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = match start_after {
+        Some(staker) => {
+            let share = user_staking(deps, &staker)?.share;
+            Some(Bound::exclusive((share.u128(), staker)))
+        }
+        None => None,
+    };
+
+    let stakers = USER_STAKING
+        .idx
+        .share
+        .keys(deps.storage, None, start, Order::Descending)
+        .take(limit)
+        .map(|staker| {
+            let staker = staker?;
+            let staker_info = user_staking(deps, &staker)?;
+            let info = settled_staker_info(
+                deps.storage,
+                &config,
+                &mut state,
+                &RewardIndexes::default(),
+                staker_info,
+                false,
+            )?;
+            Ok(StakerResponse { staker, info })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(TopStakersResponse { stakers })
+}
+
+pub fn query_staked_balance_at_height(
+    deps: Deps,
+    env: Env,
+    address: String,
+    height: Option<u64>,
+) -> StdResult<StakedBalanceAtHeightResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let height = height.unwrap_or(env.block.height);
+    let balance = STAKED_BALANCES
+        .may_load_at_height(deps.storage, &address, height)?
+        .unwrap_or_default();
+
+    Ok(StakedBalanceAtHeightResponse { balance, height })
+}
+
+pub fn query_total_staked_at_height(
+    deps: Deps,
+    env: Env,
+    height: Option<u64>,
+) -> StdResult<TotalStakedAtHeightResponse> {
+    let height = height.unwrap_or(env.block.height);
+    let total = STAKED_TOTAL
+        .may_load_at_height(deps.storage, height)?
+        .unwrap_or_default();
+
+    Ok(TotalStakedAtHeightResponse { total, height })
+}
+
+fn staker_info_response(
+    deps: Deps,
+    staker_info: StakerInfo,
+    block_time: Option<u64>,
+) -> StdResult<StakerInfoResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let mut state: State = load_state(deps)?;
+    let reward_indexes = accrued_reward_indexes(deps, &mut state, block_time)?;
+
+    settled_staker_info(
+        deps.storage,
+        &config,
+        &mut state,
+        &reward_indexes,
+        staker_info,
+        block_time.is_some(),
+    )
+}
 
+// the stored reward indexes plus what the schedules emit until block_time
+fn accrued_reward_indexes(
+    deps: Deps,
+    state: &mut State,
+    block_time: Option<u64>,
+) -> StdResult<RewardIndexes> {
+    let mut reward_indexes = RewardIndexes::load(deps.storage)?;
     if let Some(block_time) = block_time {
-        let config = CONFIG.load(deps.storage)?;
-        let mut state: State = load_state(deps)?;
+        let accrual = accrue_reward(deps.storage, state, block_time)?;
+        for (key, delta) in accrual.index {
+            *reward_indexes.current.entry(key).or_default() += delta;
+        }
+        reward_indexes.expiries.extend(accrual.expiries);
+    }
+    Ok(reward_indexes)
+}
 
-        compute_reward(&config, &mut state, block_time);
-        compute_staker_reward(&state, &mut staker_info)?;
+// settle a staker against state already accrued to the queried block time
+fn settled_staker_info(
+    storage: &dyn Storage,
+    config: &Config,
+    state: &mut State,
+    reward_indexes: &RewardIndexes,
+    mut staker_info: StakerInfo,
+    settle: bool,
+) -> StdResult<StakerInfoResponse> {
+    if settle {
+        compute_staker_reward(storage, reward_indexes, state, &mut staker_info)?;
+        if staker_info.auto_compound && !staker_info.share.is_zero() && !claim_paused(storage)? {
+            compound_reward(config, state, &mut staker_info);
+        }
+    } else {
+        refresh_stake_amount(state, &mut staker_info);
     }
 
     Ok(StakerInfoResponse {
         stake_amount: staker_info.stake_amount,
+        share: staker_info.share,
+        weight: staker_info.weight,
+        boost: staker_info.boost,
+        locks: staker_info.locks,
+        auto_compound: staker_info.auto_compound,
+        staked_at: staker_info.staked_at,
         pending_reward: staker_info.pending_reward,
         reward_index: staker_info.reward_index,
         reward_claimed: staker_info.reward_claimed,
         unlock: staker_info.unlock,
     })
 }
+
+pub fn query_game(deps: Deps, game: String) -> StdResult<GameResponse> {
+    let game_info = GAMES.load(deps.storage, &game)?;
+
+    Ok(GameResponse {
+        game,
+        revenue: game_info.revenue,
+        max_exposure: game_info.max_exposure,
+        total_payout: game_info.total_payout,
+        epoch_payout: game_info.epoch_payout,
+    })
+}
+
+pub fn query_schedule(deps: Deps, id: u64) -> StdResult<ScheduleResponse> {
+    let schedule = SCHEDULES.load(deps.storage, id)?;
+
+    Ok(schedule_response(id, schedule))
+}
+
+pub fn query_schedules(deps: Deps) -> StdResult<SchedulesResponse> {
+    let schedules = SCHEDULES
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(id, schedule)| schedule_response(id, schedule)))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(SchedulesResponse { schedules })
+}
+
+fn schedule_response(id: u64, schedule: Schedule) -> ScheduleResponse {
+    ScheduleResponse {
+        id,
+        start_date: schedule.start_date,
+        end_date: schedule.end_date,
+        amount: schedule.amount,
+        asset: schedule.asset,
+        curve: schedule.curve,
+        rebased_at: schedule.rebased_at,
+    }
+}
+
+pub fn query_games(deps: Deps) -> StdResult<GamesResponse> {
+    let games = GAMES
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            let (game, game_info) = item?;
+            Ok(GameResponse {
+                game,
+                revenue: game_info.revenue,
+                max_exposure: game_info.max_exposure,
+                total_payout: game_info.total_payout,
+                epoch_payout: game_info.epoch_payout,
+            })
+        })
+        .collect::<StdResult<Vec<GameResponse>>>()?;
+
+    Ok(GamesResponse { games })
+}
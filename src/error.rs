@@ -1,4 +1,5 @@
 use cosmwasm_std::StdError;
+use cw_utils::PaymentError;
 use thiserror::Error;
 
 #[derive(Error, Debug, PartialEq)]
@@ -6,20 +7,56 @@ pub enum ContractError {
     #[error("{0}")]
     Std(#[from] StdError),
 
+    #[error("{0}")]
+    Cw20Base(#[from] cw20_base::ContractError),
+
+    #[error("{0}")]
+    Cw721Base(#[from] cw721_base::ContractError),
+
     #[error("Unauthorized")]
     Unauthorized {},
 
+    #[error("Paused")]
+    Paused {},
+
     #[error("KART token is not enough to stake")]
     InsufficientToken {},
 
     #[error("Stake amount need to be greater than 0")]
     InvalidAmount {},
 
+    #[error("Stake is locked")]
+    StakeLocked {},
+
+    #[error("{0}")]
+    Payment(#[from] PaymentError),
+
+    #[error("Semver parsing error: {0}")]
+    SemVer(String),
+
+    #[error("Cannot migrate from {contract} {version}")]
+    InvalidMigration { contract: String, version: String },
+
+    #[error("Amount exceeds the recoverable surplus")]
+    SurplusExceeded {},
+
     #[error("Token is not supported")]
     UnsupportedToken {},
 
+    #[error("Game exposure limit exceeded")]
+    ExposureLimitExceeded {},
+
+    #[error("Bankroll drawdown cap exceeded for this epoch")]
+    DrawdownCapExceeded {},
+
     #[error("Custom Error val: {msg:?}")]
     CustomError { msg: String },
     // Add any other custom errors you like here.
     // Look at https://docs.rs/thiserror/1.0.21/thiserror/ for details.
 }
+
+impl From<semver::Error> for ContractError {
+    fn from(err: semver::Error) -> Self {
+        Self::SemVer(err.to_string())
+    }
+}
@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+
+use cosmwasm_std::{Addr, Decimal, DepsMut, Env, Order, StdResult, Uint128};
+use cw20_base::state::{TokenInfo, TOKEN_INFO};
+
+use crate::error::ContractError;
+use crate::msg::{ReceiptTokenInfo, Unlock};
+use crate::state::{
+    add_reward_index, mint_receipt, store_new_schedule, store_state, store_user_staking,
+    update_reward_totals, AssetInfo, Config, EmissionCurve, PauseFlags, Schedule, StakerInfo,
+    State, TokenType, CONFIG, EMERGENCY_WITHDRAW, OWNER, PAUSED, PAUSER,
+};
+
+/// Storage layout of 0.1, before shares, the receipt token and schedule ids.
+mod v0_1 {
+    use std::collections::HashMap;
+
+    use cosmwasm_schema::cw_serde;
+    use cosmwasm_std::{Addr, Decimal, Uint128};
+    use cw_storage_plus::{Item, Map};
+
+    #[cw_serde]
+    pub struct Config {
+        pub owner: Addr,
+        pub kart_denom: String,
+        pub unlock_time: u64,
+        pub distribution_schedule: Vec<(u64, u64, Uint128, String)>,
+    }
+
+    #[cw_serde]
+    pub struct State {
+        pub total_staker: u64,
+        pub total_stake_amount: Uint128,
+        pub last_distributed: u64,
+        pub reward_index: HashMap<String, Decimal>,
+        pub reward_distributed: HashMap<String, Uint128>,
+    }
+
+    #[cw_serde]
+    pub struct Unlock {
+        pub amount: Uint128,
+        pub at: u64,
+    }
+
+    #[cw_serde]
+    pub struct StakerInfo {
+        pub stake_amount: Uint128,
+        pub pending_reward: HashMap<String, Uint128>,
+        pub reward_index: HashMap<String, Decimal>,
+        pub reward_claimed: HashMap<String, Uint128>,
+        pub unlock: Option<Vec<Unlock>>,
+    }
+
+    pub const CONFIG: Item<Config> = Item::new("config");
+    pub const STATE: Item<State> = Item::new("state");
+    pub const USER_STAKING: Map<&str, StakerInfo> = Map::new("user_info");
+}
+
+// every staked KART became one share, the receipt token starts from those shares
+pub fn migrate_v0_1(
+    mut deps: DepsMut,
+    env: &Env,
+    receipt: Option<ReceiptTokenInfo>,
+) -> Result<(), ContractError> {
+    let receipt = receipt.ok_or(ContractError::CustomError {
+        msg: "receipt token info is required to migrate from 0.1".to_string(),
+    })?;
+
+    let config = v0_1::CONFIG.load(deps.storage)?;
+
+    OWNER.set(deps.branch(), Some(config.owner))?;
+    PAUSER.set(deps.branch(), None)?;
+    PAUSED.save(deps.storage, &PauseFlags::default())?;
+    EMERGENCY_WITHDRAW.save(deps.storage, &false)?;
+
+    // 0.1 instantiate never counted its own schedules as distributed, so the
+    // owed totals are rebuilt from the schedules themselves
+    let mut reward_distributed: HashMap<String, Uint128> = HashMap::new();
+    for (start_date, end_date, amount, denom) in config.distribution_schedule {
+        *reward_distributed.entry(denom.clone()).or_default() += amount;
+        store_new_schedule(
+            deps.storage,
+            &Schedule {
+                start_date,
+                end_date,
+                amount,
+                asset: AssetInfo::Native { denom },
+                curve: EmissionCurve::Linear,
+                rebased_at: start_date,
+            },
+        )?;
+    }
+
+    // games cannot draw from the bankroll until the owner sets a drawdown cap
+    CONFIG.save(
+        deps.storage,
+        &Config {
+            kart_denom: config.kart_denom,
+            kart_token_type: TokenType::Native,
+            unlock_time: config.unlock_time,
+            drawdown_epoch: 86400,
+            max_drawdown: Decimal::zero(),
+            position_nft: false,
+            boost_collections: vec![],
+            lock_tiers: vec![],
+            instant_unstake_penalty: Decimal::zero(),
+            penalty_treasury: None,
+            penalty_decay: 0,
+        },
+    )?;
+
+    TOKEN_INFO.save(
+        deps.storage,
+        &TokenInfo {
+            name: receipt.name,
+            symbol: receipt.symbol,
+            decimals: receipt.decimals,
+            total_supply: Uint128::zero(),
+            mint: None,
+        },
+    )?;
+
+    let state = v0_1::STATE.load(deps.storage)?;
+    let mut new_state = State {
+        total_staker: state.total_staker,
+        total_stake_amount: state.total_stake_amount,
+        total_share: state.total_stake_amount,
+        total_weight: state.total_stake_amount,
+        last_distributed: state.last_distributed,
+        epoch_start: 0,
+        epoch_bankroll: Uint128::zero(),
+        epoch_drawdown: Uint128::zero(),
+        next_unlock_id: 0,
+        total_unlock: Uint128::zero(),
+        total_unlock_share: Uint128::zero(),
+    };
+    for (denom, index) in &state.reward_index {
+        add_reward_index(deps.storage, denom, *index)?;
+    }
+    let mut reward_paid: HashMap<String, Uint128> = HashMap::new();
+
+    let stakers = v0_1::USER_STAKING
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for (staker, staker_info) in stakers {
+        let unlock = staker_info.unlock.map(|unlock| {
+            unlock
+                .into_iter()
+                .map(|unlock| {
+                    new_state.next_unlock_id += 1;
+                    new_state.total_unlock += unlock.amount;
+                    new_state.total_unlock_share += unlock.amount;
+                    Unlock {
+                        id: new_state.next_unlock_id - 1,
+                        amount: unlock.amount,
+                        share: unlock.amount,
+                        at: unlock.at,
+                    }
+                })
+                .collect()
+        });
+
+        for (denom, claimed) in &staker_info.reward_claimed {
+            *reward_paid.entry(denom.clone()).or_default() += *claimed;
+        }
+
+        // the indexed save reads the previous record, which is still in the 0.1 layout
+        v0_1::USER_STAKING.remove(deps.storage, &staker);
+
+        // the stake time is unknown, count it from the migration
+        let share = staker_info.stake_amount;
+        store_user_staking(
+            deps.storage,
+            &staker,
+            &StakerInfo {
+                stake_amount: staker_info.stake_amount,
+                share,
+                weight: share,
+                boost: None,
+                locks: vec![],
+                auto_compound: false,
+                staked_at: env.block.time.seconds(),
+                pending_reward: staker_info.pending_reward,
+                reward_index: staker_info.reward_index,
+                reward_claimed: staker_info.reward_claimed,
+                unlock,
+                stored_rewards: HashMap::new(),
+                expired_locks: vec![],
+            },
+            env.block.height,
+        )?;
+        mint_receipt(deps.storage, &Addr::unchecked(&staker), share)?;
+    }
+
+    for (denom, distributed) in reward_distributed {
+        let paid = reward_paid.remove(&denom).unwrap_or_default();
+        update_reward_totals(deps.storage, &denom, |totals| {
+            totals.distributed = distributed;
+            totals.paid = paid;
+        })?;
+    }
+    for (denom, paid) in reward_paid {
+        update_reward_totals(deps.storage, &denom, |totals| totals.paid = paid)?;
+    }
+    store_state(deps.storage, &new_state)?;
+
+    Ok(())
+}
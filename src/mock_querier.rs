@@ -21,7 +21,7 @@ pub fn mock_dependencies(
         api: MockApi::default(),
         storage: MockStorage::default(),
         querier: custom_querier,
-        custom_query_type: PhantomData::default(),
+        custom_query_type: PhantomData,
     }
 }
 
@@ -1,41 +1,283 @@
 use std::collections::HashMap;
 
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{Decimal, Uint128};
+use cosmwasm_std::{Binary, Decimal, Uint128};
+use cw20::{Cw20ReceiveMsg, Expiration};
+use cw721::Cw721ReceiveMsg;
 
-use crate::state::Config;
+use crate::state::{
+    AssetInfo, Boost, Config, EmissionCurve, LockTier, PauseFlags, StakeLock, TokenType,
+};
 
 #[cw_serde]
 pub struct InstantiateMsg {
     pub kart_denom: String,
+    pub kart_token_type: TokenType,
     pub unlock_time: u64,
-    pub distribution_schedule: Vec<(u64, u64, Uint128, String)>,
+    pub distribution_schedule: Vec<(u64, u64, Uint128, AssetInfo)>,
+    pub drawdown_epoch: u64,
+    pub max_drawdown: Decimal,
+    pub receipt_name: String,
+    pub receipt_symbol: String,
+    pub receipt_decimals: u8,
+    pub position_nft: Option<PositionNftInfo>,
+    pub boost_collections: Vec<(String, Decimal)>,
+    pub lock_tiers: Vec<LockTier>,
+    pub instant_unstake_penalty: Decimal,
+    pub penalty_treasury: Option<String>,
+    pub penalty_decay: u64,
+    pub pauser: Option<String>,
+}
+
+#[cw_serde]
+pub struct MigrateMsg {
+    // only read when migrating from 0.1, which had no receipt token
+    pub receipt: Option<ReceiptTokenInfo>,
+}
+
+#[cw_serde]
+pub struct ReceiptTokenInfo {
+    pub name: String,
+    pub symbol: String,
+    pub decimals: u8,
+}
+
+#[cw_serde]
+pub struct PositionNftInfo {
+    pub name: String,
+    pub symbol: String,
 }
 
 #[cw_serde]
 pub enum ExecuteMsg {
-    Stake {},
+    // lock_tier is an index into Config::lock_tiers
+    Stake {
+        lock_tier: Option<usize>,
+    },
+    // stake CW20 KART or fund a CW20 schedule, the hook msg is Cw20HookMsg
+    Receive(Cw20ReceiveMsg),
     Unstake {
         amount: Uint128,
     },
+    // skip the unlock period for a penalty
+    InstantUnstake {
+        amount: Uint128,
+    },
+    // unlocks are addressed by their id
     Withdraw {
-        id: usize,
+        id: u64,
+    },
+    // withdraw every matured unlock
+    WithdrawAll {},
+    // cancel pending unlocks and stake them again
+    Rebond {
+        id: u64,
     },
+    RebondAll {},
     // claim pending rewards
     Claim {},
-    Unlock {
+    // restake pending KART rewards
+    Compound {},
+    SetAutoCompound {
+        enabled: bool,
+    },
+    // owner only, moves funds that are neither staked nor owed as rewards
+    RecoverFunds {
+        asset: AssetInfo,
         amount: Uint128,
-        denom: String
     },
     SetDistribution {
-        reward_denom: String,
+        reward_asset: AssetInfo,
         start_date: u64,
         end_date: u64,
         amount: Uint128,
+        // linear when not set
+        curve: Option<EmissionCurve>,
+    },
+    // owner only, the schedule keeps streaming its remainder until the new end
+    ExtendSchedule {
+        id: u64,
+        end_date: u64,
+    },
+    // owner only, native rewards are attached, cw20 ones go through Receive
+    TopUpSchedule {
+        id: u64,
+        amount: Uint128,
+    },
+    // owner only, the part not streamed yet is refunded
+    CancelSchedule {
+        id: u64,
     },
+    // owner only, denom is the reward key, `cw20:<contract>` for CW20 rewards
+    ReclaimUndistributed {
+        denom: String,
+    },
+    // owner only, streams the undistributed rewards again in a new schedule
+    RescheduleUndistributed {
+        denom: String,
+        start_date: u64,
+        end_date: u64,
+        curve: Option<EmissionCurve>,
+    },
+    // owner only, validated like instantiate, the KART asset and position mode are fixed
     UpdateConfig {
         config: Config,
     },
+    // two-step ownership transfer, the proposed owner has to accept
+    ProposeNewOwner {
+        owner: String,
+        expiry: Option<Expiration>,
+    },
+    AcceptOwnership {},
+    CancelOwnershipProposal {},
+    // owner only
+    SetPauser {
+        pauser: Option<String>,
+    },
+    // pauser or owner, replaces all flags
+    SetPaused {
+        paused: PauseFlags,
+    },
+    // owner only
+    SetEmergencyWithdraw {
+        enabled: bool,
+    },
+    // return stake and unlocks without settling, stored pending rewards are forfeited
+    EmergencyWithdraw {},
+    EmergencyWithdrawPosition {
+        token_id: String,
+    },
+    // max_exposure caps the game's payouts within one drawdown epoch
+    AddGame {
+        game: String,
+        max_exposure: Uint128,
+    },
+    UpdateGame {
+        game: String,
+        max_exposure: Uint128,
+    },
+    RemoveGame {
+        game: String,
+    },
+    // called by a registered game with its house profit attached, cw20 revenue goes through Receive
+    DepositRevenue {},
+    // called by a registered game to pay a winner out of the staked bankroll
+    Payout {
+        recipient: String,
+        amount: Uint128,
+    },
+    // CW20 interface of the sKART receipt token, rewards follow the receipt
+    Transfer {
+        recipient: String,
+        amount: Uint128,
+    },
+    Send {
+        contract: String,
+        amount: Uint128,
+        msg: Binary,
+    },
+    IncreaseAllowance {
+        spender: String,
+        amount: Uint128,
+        expires: Option<Expiration>,
+    },
+    DecreaseAllowance {
+        spender: String,
+        amount: Uint128,
+        expires: Option<Expiration>,
+    },
+    TransferFrom {
+        owner: String,
+        recipient: String,
+        amount: Uint128,
+    },
+    SendFrom {
+        owner: String,
+        contract: String,
+        amount: Uint128,
+        msg: Binary,
+    },
+    // manage a stake position NFT, funds are paid to its current owner
+    UnstakePosition {
+        token_id: String,
+        amount: Uint128,
+    },
+    InstantUnstakePosition {
+        token_id: String,
+        amount: Uint128,
+    },
+    WithdrawPosition {
+        token_id: String,
+        id: u64,
+    },
+    WithdrawAllPosition {
+        token_id: String,
+    },
+    RebondPosition {
+        token_id: String,
+        id: u64,
+    },
+    RebondAllPosition {
+        token_id: String,
+    },
+    ClaimPosition {
+        token_id: String,
+    },
+    CompoundPosition {
+        token_id: String,
+    },
+    SetPositionAutoCompound {
+        token_id: String,
+        enabled: bool,
+    },
+    // CW721 interface of the stake positions
+    TransferNft {
+        recipient: String,
+        token_id: String,
+    },
+    SendNft {
+        contract: String,
+        token_id: String,
+        msg: Binary,
+    },
+    Approve {
+        spender: String,
+        token_id: String,
+        expires: Option<Expiration>,
+    },
+    Revoke {
+        spender: String,
+        token_id: String,
+    },
+    ApproveAll {
+        operator: String,
+        expires: Option<Expiration>,
+    },
+    RevokeAll {
+        operator: String,
+    },
+    // deposit an NFT of a boost collection, not available in position_nft mode
+    ReceiveNft(Cw721ReceiveMsg),
+    // return the boost NFT, the boost ends right away
+    WithdrawNft {},
+}
+
+#[cw_serde]
+pub enum Cw20HookMsg {
+    Stake {
+        lock_tier: Option<usize>,
+    },
+    // owner only, the sent tokens are the schedule amount
+    SetDistribution {
+        start_date: u64,
+        end_date: u64,
+        curve: Option<EmissionCurve>,
+    },
+    TopUpSchedule {
+        id: u64,
+    },
+    // registered games only, the sent tokens are the revenue
+    DepositRevenue {},
 }
 
 // query msgs
@@ -43,6 +285,14 @@ pub enum ExecuteMsg {
 #[cw_serde]
 pub enum QueryMsg {
     Config {},
+    Ownership {},
+    // rewards still owed against the balance held for them, per reward asset
+    RewardFunding {},
+    PauseStatus {},
+    Schedule {
+        id: u64,
+    },
+    Schedules {},
     State {
         block_time: Option<u64>,
     },
@@ -50,6 +300,79 @@ pub enum QueryMsg {
         staker: String,
         block_time: Option<u64>,
     },
+    // stakers ordered by address, rewards settled to block_time when set. Only stake held
+    // outside of positions, in position_nft mode the stake is listed by `Positions`
+    AllStakers {
+        start_after: Option<String>,
+        limit: Option<u32>,
+        block_time: Option<u64>,
+    },
+    // stakers ordered by stake, largest first, resuming after `start_after`. Like
+    // `AllStakers` this leaves out positions
+    TopStakers {
+        limit: Option<u32>,
+        start_after: Option<String>,
+    },
+    // voting power in staked shares, own stake plus owned positions, as of the start
+    // of `height` (default current block)
+    StakedBalanceAtHeight {
+        address: String,
+        height: Option<u64>,
+    },
+    TotalStakedAtHeight {
+        height: Option<u64>,
+    },
+    Game {
+        game: String,
+    },
+    Games {},
+    // CW20 queries of the sKART receipt token
+    Balance {
+        address: String,
+    },
+    TokenInfo {},
+    Allowance {
+        owner: String,
+        spender: String,
+    },
+    Position {
+        token_id: String,
+        block_time: Option<u64>,
+    },
+    // positions ordered by token id with their NFT owner, rewards settled to block_time when set
+    Positions {
+        start_after: Option<String>,
+        limit: Option<u32>,
+        block_time: Option<u64>,
+    },
+    // CW721 queries of the stake positions
+    OwnerOf {
+        token_id: String,
+        include_expired: Option<bool>,
+    },
+    Approval {
+        token_id: String,
+        spender: String,
+        include_expired: Option<bool>,
+    },
+    NumTokens {},
+    ContractInfo {},
+    NftInfo {
+        token_id: String,
+    },
+    AllNftInfo {
+        token_id: String,
+        include_expired: Option<bool>,
+    },
+    Tokens {
+        owner: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    AllTokens {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
 }
 
 // We define a custom struct for each query response
@@ -59,31 +382,163 @@ pub struct ConfigResponse {
 
     pub kart_denom: String,
 
+    pub kart_token_type: TokenType,
+
     pub unlock_time: u64,
 
-    pub distribution_schedule: Vec<(u64, u64, Uint128, String)>,
+    pub drawdown_epoch: u64,
+
+    pub max_drawdown: Decimal,
+
+    pub position_nft: bool,
+
+    pub boost_collections: Vec<(String, Decimal)>,
+
+    pub lock_tiers: Vec<LockTier>,
+
+    pub instant_unstake_penalty: Decimal,
+
+    pub penalty_treasury: Option<String>,
+
+    pub penalty_decay: u64,
 }
 
 #[cw_serde]
 pub struct StateResponse {
     pub total_staker: u64,
     pub total_stake_amount: Uint128,
+    pub total_share: Uint128,
+    pub total_weight: Uint128,
     pub last_distributed: u64,
     pub reward_index: HashMap<String, Decimal>,
     pub reward_distributed: HashMap<String, Uint128>,
+    pub epoch_start: u64,
+    pub epoch_bankroll: Uint128,
+    pub epoch_drawdown: Uint128,
+    pub next_unlock_id: u64,
+    pub total_unlock: Uint128,
+    pub total_unlock_share: Uint128,
+    pub reward_paid: HashMap<String, Uint128>,
+    pub undistributed: HashMap<String, Uint128>,
 }
 
 #[cw_serde]
 pub struct Unlock {
+    pub id: u64,
+    // KART value of the share, refreshed whenever the staker is settled
     pub amount: Uint128,
+    pub share: Uint128,
     pub at: u64,
 }
 
 #[cw_serde]
 pub struct StakerInfoResponse {
     pub stake_amount: Uint128,
+    pub share: Uint128,
+    pub weight: Uint128,
+    pub boost: Option<Boost>,
+    pub locks: Vec<StakeLock>,
+    pub auto_compound: bool,
+    pub staked_at: u64,
     pub pending_reward: HashMap<String, Uint128>,
     pub reward_index: HashMap<String, Decimal>,
     pub reward_claimed: HashMap<String, Uint128>,
     pub unlock: Option<Vec<Unlock>>,
 }
+
+#[cw_serde]
+pub struct StakerResponse {
+    pub staker: String,
+    pub info: StakerInfoResponse,
+}
+
+#[cw_serde]
+pub struct AllStakersResponse {
+    pub stakers: Vec<StakerResponse>,
+}
+
+#[cw_serde]
+pub struct TopStakersResponse {
+    pub stakers: Vec<StakerResponse>,
+}
+
+#[cw_serde]
+pub struct PositionResponse {
+    pub token_id: String,
+    pub owner: String,
+    pub info: StakerInfoResponse,
+}
+
+#[cw_serde]
+pub struct PositionsResponse {
+    pub positions: Vec<PositionResponse>,
+}
+
+#[cw_serde]
+pub struct StakedBalanceAtHeightResponse {
+    pub balance: Uint128,
+    pub height: u64,
+}
+
+#[cw_serde]
+pub struct TotalStakedAtHeightResponse {
+    pub total: Uint128,
+    pub height: u64,
+}
+
+#[cw_serde]
+pub struct GameResponse {
+    pub game: String,
+    pub revenue: HashMap<String, Uint128>,
+    pub max_exposure: Uint128,
+    pub total_payout: Uint128,
+    pub epoch_payout: Uint128,
+}
+
+#[cw_serde]
+pub struct GamesResponse {
+    pub games: Vec<GameResponse>,
+}
+
+#[cw_serde]
+pub struct ScheduleResponse {
+    pub id: u64,
+    pub start_date: u64,
+    pub end_date: u64,
+    pub amount: Uint128,
+    pub asset: AssetInfo,
+    pub curve: EmissionCurve,
+    pub rebased_at: u64,
+}
+
+#[cw_serde]
+pub struct SchedulesResponse {
+    pub schedules: Vec<ScheduleResponse>,
+}
+
+#[cw_serde]
+pub struct RewardFunding {
+    pub asset: AssetInfo,
+    pub owed: Uint128,
+    pub balance: Uint128,
+    pub shortfall: Uint128,
+}
+
+#[cw_serde]
+pub struct RewardFundingResponse {
+    pub funding: Vec<RewardFunding>,
+}
+
+#[cw_serde]
+pub struct PauseStatusResponse {
+    pub pauser: Option<String>,
+    pub paused: PauseFlags,
+    pub emergency_withdraw: bool,
+}
+
+#[cw_serde]
+pub struct OwnershipResponse {
+    pub owner: String,
+    pub pending_owner: Option<String>,
+    pub pending_expiry: Option<Expiration>,
+}
@@ -1,56 +1,529 @@
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
 
 use cosmwasm_schema::cw_serde;
 
-use cosmwasm_std::{Addr, Decimal, Deps, Response, StdResult, Storage, Uint128};
-use cw_storage_plus::{Item, Map};
+use cosmwasm_std::{
+    to_json_binary, Addr, BankMsg, Coin, CosmosMsg, Decimal, Deps, Empty, Order, Response,
+    StdError, StdResult, Storage, Uint128, WasmMsg,
+};
+use cw20::Cw20ExecuteMsg;
+use cw20_base::state::{BALANCES, TOKEN_INFO};
+use cw721_base::{Cw721Contract, ExecuteMsg as Cw721ExecuteMsg, Extension};
+use cw_controllers::Admin;
+use cw_storage_plus::{
+    Index, IndexList, IndexedMap, Item, Map, MultiIndex, Prefix, SnapshotItem, SnapshotMap,
+    Strategy,
+};
+use cw_utils::Expiration;
 
 use crate::msg::Unlock;
 
+/// How the staking asset is held. For `Native` the `kart_denom` is a bank denom,
+/// for `Cw20` it is the address of the token contract.
 #[cw_serde]
-pub struct Config {
-    pub owner: Addr,
+pub enum TokenType {
+    Native,
+    Cw20,
+}
+
+const CW20_KEY_PREFIX: &str = "cw20:";
+
+/// A reward asset. Reward maps are keyed by its string form: the bare denom
+/// for native tokens and `cw20:<contract>` for CW20 tokens.
+#[cw_serde]
+pub enum AssetInfo {
+    Native { denom: String },
+    Cw20 { contract_addr: Addr },
+}
+
+impl AssetInfo {
+    pub fn from_key(key: &str) -> Self {
+        match key.strip_prefix(CW20_KEY_PREFIX) {
+            Some(contract_addr) => AssetInfo::Cw20 {
+                contract_addr: Addr::unchecked(contract_addr),
+            },
+            None => AssetInfo::Native {
+                denom: key.to_string(),
+            },
+        }
+    }
+
+    pub fn transfer_msg(&self, recipient: &Addr, amount: Uint128) -> StdResult<CosmosMsg> {
+        match self {
+            AssetInfo::Native { denom } => Ok(BankMsg::Send {
+                to_address: recipient.to_string(),
+                amount: vec![Coin {
+                    denom: denom.clone(),
+                    amount,
+                }],
+            }
+            .into()),
+            AssetInfo::Cw20 { contract_addr } => Ok(WasmMsg::Execute {
+                contract_addr: contract_addr.to_string(),
+                msg: to_json_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: recipient.to_string(),
+                    amount,
+                })?,
+                funds: vec![],
+            }
+            .into()),
+        }
+    }
+}
+
+impl fmt::Display for AssetInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AssetInfo::Native { denom } => write!(f, "{}", denom),
+            AssetInfo::Cw20 { contract_addr } => write!(f, "{}{}", CW20_KEY_PREFIX, contract_addr),
+        }
+    }
+}
 
+#[cw_serde]
+pub struct Config {
     pub kart_denom: String,
 
+    pub kart_token_type: TokenType,
+
     pub unlock_time: u64,
 
-    pub distribution_schedule: Vec<(u64, u64, Uint128, String)>,
+    // length in seconds of a bankroll drawdown epoch
+    pub drawdown_epoch: u64,
+
+    // share of the bankroll games can draw within one epoch
+    pub max_drawdown: Decimal,
+
+    // mint every stake as a separate, transferable CW721 position
+    pub position_nft: bool,
+
+    // NFT collections that boost the reward weight of a staker
+    pub boost_collections: Vec<(Addr, Decimal)>,
+
+    // lock periods a stake can opt into for a higher reward weight
+    pub lock_tiers: Vec<LockTier>,
+
+    // share of an instant unstake withheld as penalty
+    pub instant_unstake_penalty: Decimal,
+
+    // the penalty goes here, it is redistributed to the stakers when None
+    pub penalty_treasury: Option<Addr>,
+
+    // seconds of staking over which the penalty decays to zero, 0 disables the decay
+    pub penalty_decay: u64,
+}
+
+#[cw_serde]
+pub struct LockTier {
+    pub duration: u64,
+    pub multiplier: Decimal,
+}
+
+impl Config {
+    pub fn kart_asset(&self) -> AssetInfo {
+        match self.kart_token_type {
+            TokenType::Native => AssetInfo::Native {
+                denom: self.kart_denom.clone(),
+            },
+            TokenType::Cw20 => AssetInfo::Cw20 {
+                contract_addr: Addr::unchecked(&self.kart_denom),
+            },
+        }
+    }
 }
 
+/// Stakers own shares of the bonded KART bankroll, so a game payout lowers every
+/// stake in proportion. Reward indexes are accrued per unit of weight, which is
+/// the share scaled by any boost.
+///
+/// Reward indexes and totals are stored per denom in `REWARD_INDEX` and
+/// `REWARD_TOTALS`, and only the denoms a call touches are read or written.
 #[cw_serde]
 pub struct State {
     pub total_staker: u64,
     pub total_stake_amount: Uint128,
+    pub total_share: Uint128,
+    pub total_weight: Uint128,
     pub last_distributed: u64,
-    pub reward_index: HashMap<String, Decimal>,
-    pub reward_distributed: HashMap<String, Uint128>,
+    pub epoch_start: u64,
+    pub epoch_bankroll: Uint128,
+    pub epoch_drawdown: Uint128,
+    // unlock ids are global and never reused
+    pub next_unlock_id: u64,
+    // KART of pending unlocks, outside of total_stake_amount but still in the bankroll
+    pub total_unlock: Uint128,
+    // unlocks hold shares of total_unlock, so payouts reach them too
+    pub total_unlock_share: Uint128,
+}
+
+/// Reward bookkeeping of one denom.
+#[cw_serde]
+#[derive(Default)]
+pub struct RewardTotals {
+    pub distributed: Uint128,
+    // paid out or compounded, distributed minus this is still owed
+    pub paid: Uint128,
+    // emitted while nothing was staked, kept for the owner to reclaim or reschedule
+    pub undistributed: Uint128,
+}
+
+/// Shape of the emission rate over a schedule, times are seconds since its
+/// `start_date` and rates are relative.
+#[cw_serde]
+pub enum EmissionCurve {
+    Linear,
+    // the rate halves every `half_life` seconds
+    ExponentialDecay { half_life: u64 },
+    // one rate per epoch, the last one holds until the end
+    Steps { epoch: u64, rates: Vec<Decimal> },
+    // (time, rate) points with the rate interpolated in between, the last rate
+    // holds until the end
+    Piecewise { points: Vec<(u64, Decimal)> },
+}
+
+impl EmissionCurve {
+    pub fn validate(&self) -> StdResult<()> {
+        let valid = match self {
+            EmissionCurve::Linear => true,
+            EmissionCurve::ExponentialDecay { half_life } => *half_life > 0,
+            EmissionCurve::Steps { epoch, rates } => *epoch > 0 && !rates.is_empty(),
+            EmissionCurve::Piecewise { points } => {
+                points.first().is_some_and(|point| point.0 == 0)
+                    && points.windows(2).all(|pair| pair[0].0 < pair[1].0)
+            }
+        };
+
+        if !valid {
+            return Err(StdError::generic_err("invalid emission curve"));
+        }
+
+        Ok(())
+    }
+
+    // emission from the start of the curve up to `t`
+    pub fn integral(&self, t: u64) -> Decimal {
+        match self {
+            EmissionCurve::Linear => Decimal::from_ratio(t, 1u64),
+            EmissionCurve::ExponentialDecay { half_life } => {
+                Decimal::one()
+                    - pow2_neg(
+                        t / half_life,
+                        Decimal::from_ratio(t % half_life, *half_life),
+                    )
+            }
+            EmissionCurve::Steps { epoch, rates } => {
+                let full = (t / epoch) as usize;
+                let last = rates[rates.len() - 1];
+                let rate = |i: usize| rates.get(i).copied().unwrap_or(last);
+
+                let mut total = Decimal::zero();
+                for i in 0..std::cmp::min(full, rates.len()) {
+                    total += rate(i) * Decimal::from_ratio(*epoch, 1u64);
+                }
+                if full > rates.len() {
+                    total += last * Decimal::from_ratio((full - rates.len()) as u64 * epoch, 1u64);
+                }
+                total + rate(full) * Decimal::from_ratio(t % epoch, 1u64)
+            }
+            EmissionCurve::Piecewise { points } => {
+                let mut total = Decimal::zero();
+                for pair in points.windows(2) {
+                    let ((x0, r0), (x1, r1)) = (pair[0], pair[1]);
+                    if t <= x0 {
+                        return total;
+                    }
+
+                    let x = std::cmp::min(t, x1);
+                    let ratio = Decimal::from_ratio(x - x0, x1 - x0);
+                    let r = if r1 >= r0 {
+                        r0 + (r1 - r0) * ratio
+                    } else {
+                        r0 - (r0 - r1) * ratio
+                    };
+                    total += (r0 + r) * Decimal::from_ratio(x - x0, 2u64);
+                }
+
+                let (x, r) = points[points.len() - 1];
+                total + r * Decimal::from_ratio(t.saturating_sub(x), 1u64)
+            }
+        }
+    }
+}
+
+// 2^-(n + frac) for frac in [0, 1)
+fn pow2_neg(n: u64, frac: Decimal) -> Decimal {
+    // beyond the precision of Decimal
+    if n >= 64 {
+        return Decimal::zero();
+    }
+
+    // e^-y as a Taylor series, y = frac * ln 2 stays below 1
+    let y = frac * Decimal::from_atomics(693_147_180_559_945_309u128, 18).unwrap();
+    let (mut even, mut odd) = (Decimal::one(), Decimal::zero());
+    let mut term = Decimal::one();
+    for k in 1..40u64 {
+        term = term * y / Decimal::from_ratio(k, 1u64);
+        if term.is_zero() {
+            break;
+        }
+        if k % 2 == 0 {
+            even += term;
+        } else {
+            odd += term;
+        }
+    }
+
+    (even - odd) / Decimal::from_ratio(1u128 << n, 1u128)
+}
+
+/// Reward streamed between `start_date` and `end_date` along `curve`. Extending
+/// or topping up a running schedule rebases it, `amount` is what is left to be
+/// streamed from `rebased_at` on.
+#[cw_serde]
+pub struct Schedule {
+    pub start_date: u64,
+    pub end_date: u64,
+    pub amount: Uint128,
+    pub asset: AssetInfo,
+    pub curve: EmissionCurve,
+    pub rebased_at: u64,
+}
+
+impl Schedule {
+    // amount streamed between `from` and `to`, both within rebased_at..=end_date
+    pub fn emitted(&self, from: u64, to: u64) -> Uint128 {
+        let integral = |t: u64| self.curve.integral(t - self.start_date);
+
+        let remaining = integral(self.end_date) - integral(self.rebased_at);
+        if remaining.is_zero() {
+            return Uint128::zero();
+        }
+
+        let part = integral(to) - integral(from);
+        self.amount
+            .multiply_ratio(part.atomics(), remaining.atomics())
+    }
+}
+
+/// A deposited NFT raising the reward weight of its staker.
+#[cw_serde]
+pub struct Boost {
+    pub collection: Addr,
+    pub token_id: String,
+    pub multiplier: Decimal,
+}
+
+impl Boost {
+    // hands the NFT back to `recipient`
+    pub fn return_msg(&self, recipient: &Addr) -> StdResult<CosmosMsg> {
+        Ok(WasmMsg::Execute {
+            contract_addr: self.collection.to_string(),
+            msg: to_json_binary(&Cw721ExecuteMsg::<Extension, Empty>::TransferNft {
+                recipient: recipient.to_string(),
+                token_id: self.token_id.clone(),
+            })?,
+            funds: vec![],
+        }
+        .into())
+    }
+}
+
+/// Share that cannot be unstaked before `until`. The multiplier stops applying
+/// at `until`, when the extra weight leaves `total_weight` through `LOCK_EXPIRIES`.
+#[cw_serde]
+pub struct StakeLock {
+    pub share: Uint128,
+    pub multiplier: Decimal,
+    pub until: u64,
 }
 
+/// The reward maps are stored per denom in `USER_REWARDS` or `POSITION_REWARDS`,
+/// only the denoms whose record changed since loading are written back.
 #[cw_serde]
+#[derive(Default)]
 pub struct StakerInfo {
+    // KART value of `share`, refreshed whenever the position is settled
     pub stake_amount: Uint128,
+    pub share: Uint128,
+    pub weight: Uint128,
+    pub boost: Option<Boost>,
+    pub locks: Vec<StakeLock>,
+    // restake KART rewards whenever the staker is settled
+    pub auto_compound: bool,
+    // share weighted average time the stake was made
+    pub staked_at: u64,
+    #[serde(skip)]
     pub pending_reward: HashMap<String, Uint128>,
+    #[serde(skip)]
     pub reward_index: HashMap<String, Decimal>,
+    #[serde(skip)]
     pub reward_claimed: HashMap<String, Uint128>,
     pub unlock: Option<Vec<Unlock>>,
+    // reward records as loaded
+    #[serde(skip)]
+    pub stored_rewards: HashMap<String, StakerReward>,
+    // expiries of the locks dropped since loading, released when the record is written
+    #[serde(skip)]
+    pub expired_locks: Vec<u64>,
+}
+
+/// Locks expiring at one time. `weight` leaves `total_weight` at that time, the entry
+/// and its `EXPIRY_INDEX` are kept until the last of the `locks` is released.
+#[cw_serde]
+#[derive(Default)]
+pub struct LockExpiry {
+    pub weight: Uint128,
+    pub locks: u32,
+}
+
+/// Reward checkpoint of a staker for one denom.
+#[cw_serde]
+#[derive(Default)]
+pub struct StakerReward {
+    pub index: Decimal,
+    pub pending: Uint128,
+    pub claimed: Uint128,
+}
+
+/// A casino game contract allowed to share its house revenue with stakers.
+#[cw_serde]
+pub struct GameInfo {
+    pub revenue: HashMap<String, Uint128>,
+    pub max_exposure: Uint128,
+    pub total_payout: Uint128,
+    // payouts in the drawdown epoch starting at epoch_start, capped by max_exposure
+    pub epoch_start: u64,
+    pub epoch_payout: Uint128,
 }
 
+impl StakerInfo {
+    pub fn locked_share(&self) -> Uint128 {
+        self.locks.iter().map(|lock| lock.share).sum()
+    }
+
+    pub fn boost_multiplier(&self) -> Decimal {
+        self.boost
+            .as_ref()
+            .map(|boost| boost.multiplier)
+            .unwrap_or(Decimal::one())
+    }
+
+    // weight a lock adds on top of its share until it expires
+    pub fn lock_bonus(&self, lock: &StakeLock) -> Uint128 {
+        lock.share * (lock.multiplier.saturating_sub(Decimal::one()) * self.boost_multiplier())
+    }
+}
+
+/// Owner proposed by the current one, takes over once it accepts.
+#[cw_serde]
+pub struct PendingOwner {
+    pub owner: Addr,
+    pub expiry: Option<Expiration>,
+}
+
+// owner is kept out of Config so update_config cannot hand it over
+pub const OWNER: Admin = Admin::new("owner");
+pub const PENDING_OWNER: Item<PendingOwner> = Item::new("pending_owner");
+/// Actions the pauser has stopped.
+#[cw_serde]
+#[derive(Default)]
+pub struct PauseFlags {
+    pub stake: bool,
+    pub unstake: bool,
+    // also keeps full exits and auto-compound from paying out, the rewards stay pending
+    pub claim: bool,
+    pub withdraw: bool,
+    pub set_distribution: bool,
+}
+
+// lets stakers pull their principal without settling rewards
+pub const EMERGENCY_WITHDRAW: Item<bool> = Item::new("emergency_withdraw");
+pub const PAUSER: Admin = Admin::new("pauser");
+pub const PAUSED: Item<PauseFlags> = Item::new("paused");
 pub const CONFIG: Item<Config> = Item::new("config");
 pub const STATE: Item<State> = Item::new("state");
-pub const USER_STAKING: Map<&str, StakerInfo> = Map::new("user_info");
+pub const REWARD_INDEX: Map<&str, Decimal> = Map::new("reward_index");
+pub const REWARD_TOTALS: Map<&str, RewardTotals> = Map::new("reward_totals");
+// staked shares per address and their sum, checkpointed for governance voting power.
+// Shares rather than KART so a snapshot is not outdated by later payouts. An address
+// holds its own stake plus the shares of the position NFTs it owns
+pub const STAKED_BALANCES: SnapshotMap<&Addr, Uint128> = SnapshotMap::new(
+    "staked_balances",
+    "staked_balances__checkpoints",
+    "staked_balances__changelog",
+    Strategy::EveryBlock,
+);
+pub const STAKED_TOTAL: SnapshotItem<Uint128> = SnapshotItem::new(
+    "total_staked",
+    "total_staked__checkpoints",
+    "total_staked__changelog",
+    Strategy::EveryBlock,
+);
+pub const LOCK_EXPIRIES: Map<u64, LockExpiry> = Map::new("lock_expiries");
+// reward index per denom at a lock expiry, lock holders settle their boosted part up to it
+pub const EXPIRY_INDEX: Map<(u64, &str), Decimal> = Map::new("expiry_index");
+pub const USER_REWARDS: Map<(&Addr, &str), StakerReward> = Map::new("user_rewards");
+pub const GAMES: Map<&str, GameInfo> = Map::new("games");
+// stake positions keyed by NFT token id, only used when `Config::position_nft` is set
+pub const POSITIONS: Map<&str, StakerInfo> = Map::new("positions");
+pub const POSITION_REWARDS: Map<(&str, &str), StakerReward> = Map::new("position_rewards");
+pub const POSITION_SEQ: Item<u64> = Item::new("position_seq");
+// distribution schedules keyed by id, ids are never reused
+pub const SCHEDULES: Map<u64, Schedule> = Map::new("schedules");
+pub const SCHEDULE_SEQ: Item<u64> = Item::new("schedule_seq");
+
+pub struct StakerIndexes<'a> {
+    // ordering by share is ordering by KART value, and unlike `stake_amount`
+    // it does not go stale when payouts move the share price
+    pub share: MultiIndex<'a, u128, StakerInfo, String>,
+}
+
+impl<'a> IndexList<StakerInfo> for StakerIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<StakerInfo>> + '_> {
+        let v: Vec<&dyn Index<StakerInfo>> = vec![&self.share];
+        Box::new(v.into_iter())
+    }
+}
+
+fn staker_share(_pk: &[u8], staker_info: &StakerInfo) -> u128 {
+    staker_info.share.u128()
+}
+
+pub const USER_STAKING: IndexedMap<&str, StakerInfo, StakerIndexes> = IndexedMap::new(
+    "user_info",
+    StakerIndexes {
+        share: MultiIndex::new(staker_share, "user_info", "user_info__share"),
+    },
+);
+
+pub fn position_nft() -> Cw721Contract<'static, Extension, Empty, Empty, Empty> {
+    Cw721Contract::default()
+}
 
 pub fn user_staking(deps: Deps, sender: &str) -> StdResult<StakerInfo> {
     let staking_info = USER_STAKING.may_load(deps.storage, sender).unwrap();
 
     match staking_info {
-        Some(staking_info) => Ok(staking_info),
+        Some(mut staking_info) => {
+            let owner = Addr::unchecked(sender);
+            read_staker_rewards(deps.storage, USER_REWARDS.prefix(&owner), &mut staking_info)?;
+            Ok(staking_info)
+        }
         None => Ok(StakerInfo {
             stake_amount: Uint128::zero(),
+            share: Uint128::zero(),
+            weight: Uint128::zero(),
+            boost: None,
+            locks: vec![],
+            auto_compound: false,
+            staked_at: 0,
             pending_reward: HashMap::new(),
             reward_index: HashMap::new(),
             reward_claimed: HashMap::new(),
             unlock: None,
+            stored_rewards: HashMap::new(),
+            expired_locks: vec![],
         }),
     }
 }
@@ -59,22 +532,275 @@ pub fn store_user_staking(
     storage: &mut dyn Storage,
     owner: &str,
     staker_info: &StakerInfo,
+    height: u64,
 ) -> StdResult<Response> {
+    let previous = USER_STAKING
+        .may_load(storage, owner)?
+        .map(|staker_info| staker_info.share)
+        .unwrap_or_default();
     USER_STAKING.save(storage, owner, staker_info)?;
+
+    let owner = Addr::unchecked(owner);
+    snapshot_stake(storage, &owner, previous, staker_info.share, height)?;
+    release_lock_expiries(storage, staker_info)?;
+    for (denom, reward) in changed_rewards(staker_info) {
+        USER_REWARDS.save(storage, (&owner, &denom), &reward)?;
+    }
     Ok(Response::new())
 }
 
-pub fn remove_user_staking(storage: &mut dyn Storage, owner: &str) -> StdResult<Response> {
-    USER_STAKING.remove(storage, owner);
+pub fn remove_user_staking(
+    storage: &mut dyn Storage,
+    owner: &str,
+    staker_info: &StakerInfo,
+    height: u64,
+) -> StdResult<Response> {
+    let previous = USER_STAKING
+        .may_load(storage, owner)?
+        .map(|staker_info| staker_info.share)
+        .unwrap_or_default();
+    USER_STAKING.remove(storage, owner)?;
+
+    let owner = Addr::unchecked(owner);
+    snapshot_stake(storage, &owner, previous, Uint128::zero(), height)?;
+    release_lock_expiries(storage, staker_info)?;
+    let denoms = USER_REWARDS
+        .prefix(&owner)
+        .keys(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for denom in denoms {
+        USER_REWARDS.remove(storage, (&owner, &denom));
+    }
     Ok(Response::new())
 }
 
+// replace `previous` shares of an owner with `share` at `height`, keeping the total in step
+pub fn snapshot_stake(
+    storage: &mut dyn Storage,
+    owner: &Addr,
+    previous: Uint128,
+    share: Uint128,
+    height: u64,
+) -> StdResult<()> {
+    if previous == share {
+        return Ok(());
+    }
+
+    let balance = STAKED_BALANCES
+        .may_load(storage, owner)?
+        .unwrap_or_default();
+    STAKED_BALANCES.save(storage, owner, &(balance + share - previous), height)?;
+    let total = STAKED_TOTAL.may_load(storage)?.unwrap_or_default();
+    STAKED_TOTAL.save(storage, &(total + share - previous), height)
+}
+
+pub fn position(storage: &dyn Storage, token_id: &str) -> StdResult<StakerInfo> {
+    let mut position = POSITIONS.load(storage, token_id)?;
+    read_staker_rewards(storage, POSITION_REWARDS.prefix(token_id), &mut position)?;
+    Ok(position)
+}
+
+pub fn store_position(
+    storage: &mut dyn Storage,
+    owner: &Addr,
+    token_id: &str,
+    position: &StakerInfo,
+    height: u64,
+) -> StdResult<()> {
+    let previous = POSITIONS
+        .may_load(storage, token_id)?
+        .map(|position| position.share)
+        .unwrap_or_default();
+    POSITIONS.save(storage, token_id, position)?;
+    snapshot_stake(storage, owner, previous, position.share, height)?;
+    release_lock_expiries(storage, position)?;
+
+    for (denom, reward) in changed_rewards(position) {
+        POSITION_REWARDS.save(storage, (token_id, &denom), &reward)?;
+    }
+    Ok(())
+}
+
+pub fn remove_position(
+    storage: &mut dyn Storage,
+    owner: &Addr,
+    token_id: &str,
+    position: &StakerInfo,
+    height: u64,
+) -> StdResult<()> {
+    if let Some(position) = POSITIONS.may_load(storage, token_id)? {
+        snapshot_stake(storage, owner, position.share, Uint128::zero(), height)?;
+    }
+    POSITIONS.remove(storage, token_id);
+    release_lock_expiries(storage, position)?;
+
+    let denoms = POSITION_REWARDS
+        .prefix(token_id)
+        .keys(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for denom in denoms {
+        POSITION_REWARDS.remove(storage, (token_id, &denom));
+    }
+    Ok(())
+}
+
+// the expiry index is dropped with the last lock expiring at that time
+fn release_lock_expiries(storage: &mut dyn Storage, staker_info: &StakerInfo) -> StdResult<()> {
+    for until in &staker_info.expired_locks {
+        let mut expiry = LOCK_EXPIRIES.may_load(storage, *until)?.unwrap_or_default();
+        expiry.locks = expiry.locks.saturating_sub(1);
+        if expiry.locks > 0 {
+            LOCK_EXPIRIES.save(storage, *until, &expiry)?;
+            continue;
+        }
+
+        LOCK_EXPIRIES.remove(storage, *until);
+        let denoms = EXPIRY_INDEX
+            .prefix(*until)
+            .keys(storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+        for denom in denoms {
+            EXPIRY_INDEX.remove(storage, (*until, &denom));
+        }
+    }
+    Ok(())
+}
+
+fn read_staker_rewards(
+    storage: &dyn Storage,
+    prefix: Prefix<&str, StakerReward, &str>,
+    staker_info: &mut StakerInfo,
+) -> StdResult<()> {
+    for item in prefix.range(storage, None, None, Order::Ascending) {
+        let (denom, reward) = item?;
+        insert_nonzero(&mut staker_info.reward_index, &denom, reward.index);
+        insert_nonzero(&mut staker_info.pending_reward, &denom, reward.pending);
+        insert_nonzero(&mut staker_info.reward_claimed, &denom, reward.claimed);
+        staker_info.stored_rewards.insert(denom, reward);
+    }
+    Ok(())
+}
+
+// a zero entry reads the same as a missing one, keep the maps as they were built
+pub fn insert_nonzero<T: PartialEq + Default>(map: &mut HashMap<String, T>, denom: &str, value: T) {
+    if value != T::default() {
+        map.insert(denom.to_string(), value);
+    }
+}
+
+// the checkpoints that differ from the stored ones
+fn changed_rewards(staker_info: &StakerInfo) -> Vec<(String, StakerReward)> {
+    let denoms: BTreeSet<&String> = staker_info
+        .reward_index
+        .keys()
+        .chain(staker_info.pending_reward.keys())
+        .chain(staker_info.reward_claimed.keys())
+        .chain(staker_info.stored_rewards.keys())
+        .collect();
+
+    denoms
+        .into_iter()
+        .map(|denom| {
+            let reward = StakerReward {
+                index: staker_info
+                    .reward_index
+                    .get(denom)
+                    .copied()
+                    .unwrap_or_default(),
+                pending: staker_info
+                    .pending_reward
+                    .get(denom)
+                    .copied()
+                    .unwrap_or_default(),
+                claimed: staker_info
+                    .reward_claimed
+                    .get(denom)
+                    .copied()
+                    .unwrap_or_default(),
+            };
+            (denom.clone(), reward)
+        })
+        .filter(|(denom, reward)| {
+            staker_info
+                .stored_rewards
+                .get(denom)
+                .cloned()
+                .unwrap_or_default()
+                != *reward
+        })
+        .collect()
+}
+
+// the sKART receipt balance of a staker always equals its bankroll share
+pub fn mint_receipt(storage: &mut dyn Storage, owner: &Addr, share: Uint128) -> StdResult<()> {
+    BALANCES.update(storage, owner, |balance| -> StdResult<_> {
+        Ok(balance.unwrap_or_default() + share)
+    })?;
+    TOKEN_INFO.update(storage, |mut token_info| -> StdResult<_> {
+        token_info.total_supply += share;
+        Ok(token_info)
+    })?;
+    Ok(())
+}
+
+pub fn burn_receipt(storage: &mut dyn Storage, owner: &Addr, share: Uint128) -> StdResult<()> {
+    BALANCES.update(storage, owner, |balance| -> StdResult<_> {
+        Ok(balance.unwrap_or_default().checked_sub(share)?)
+    })?;
+    TOKEN_INFO.update(storage, |mut token_info| -> StdResult<_> {
+        token_info.total_supply = token_info.total_supply.checked_sub(share)?;
+        Ok(token_info)
+    })?;
+    Ok(())
+}
+
+// schedule ids count up from 0
+pub fn store_new_schedule(storage: &mut dyn Storage, schedule: &Schedule) -> StdResult<u64> {
+    let id = SCHEDULE_SEQ.may_load(storage)?.unwrap_or_default();
+    SCHEDULES.save(storage, id, schedule)?;
+    SCHEDULE_SEQ.save(storage, &(id + 1))?;
+    Ok(id)
+}
+
 pub fn load_state(deps: Deps) -> StdResult<State> {
     let state_info = STATE.load(deps.storage).unwrap();
-    return Ok(state_info);
+    Ok(state_info)
 }
 
 pub fn store_state(storage: &mut dyn Storage, state: &State) -> StdResult<Response> {
     STATE.save(storage, state).unwrap();
-    return Ok(Response::new());
+    Ok(Response::new())
+}
+
+// the reward index of every denom, settling a staker needs all of them
+pub fn reward_indexes(storage: &dyn Storage) -> StdResult<HashMap<String, Decimal>> {
+    REWARD_INDEX
+        .range(storage, None, None, Order::Ascending)
+        .collect()
+}
+
+pub fn add_reward_index(storage: &mut dyn Storage, key: &str, delta: Decimal) -> StdResult<()> {
+    REWARD_INDEX.update(storage, key, |index| -> StdResult<_> {
+        Ok(index.unwrap_or_default() + delta)
+    })?;
+    Ok(())
+}
+
+pub fn all_reward_totals(storage: &dyn Storage) -> StdResult<Vec<(String, RewardTotals)>> {
+    REWARD_TOTALS
+        .range(storage, None, None, Order::Ascending)
+        .collect()
+}
+
+pub fn reward_totals(storage: &dyn Storage, key: &str) -> StdResult<RewardTotals> {
+    Ok(REWARD_TOTALS.may_load(storage, key)?.unwrap_or_default())
+}
+
+pub fn update_reward_totals<F>(storage: &mut dyn Storage, key: &str, action: F) -> StdResult<()>
+where
+    F: FnOnce(&mut RewardTotals),
+{
+    let mut totals = reward_totals(storage, key)?;
+    action(&mut totals);
+    REWARD_TOTALS.save(storage, key, &totals)
 }
@@ -1,14 +1,29 @@
 use std::collections::HashMap;
 
-use crate::contract::{execute, instantiate, query};
+use crate::contract::{execute, instantiate, migrate, query};
 use crate::error::ContractError;
 use crate::mock_querier::mock_dependencies;
 use crate::msg::{
-    ConfigResponse, ExecuteMsg, InstantiateMsg, QueryMsg, StakerInfoResponse, StateResponse, Unlock,
+    AllStakersResponse, ConfigResponse, Cw20HookMsg, ExecuteMsg, GameResponse, InstantiateMsg,
+    MigrateMsg, OwnershipResponse, PauseStatusResponse, PositionNftInfo, PositionsResponse,
+    QueryMsg, ReceiptTokenInfo, RewardFunding, RewardFundingResponse, ScheduleResponse,
+    SchedulesResponse, StakedBalanceAtHeightResponse, StakerInfoResponse, StateResponse,
+    TopStakersResponse, TotalStakedAtHeightResponse, Unlock,
+};
+use crate::state::{
+    AssetInfo, Config, EmissionCurve, LockTier, PauseFlags, StakerReward, TokenType, EXPIRY_INDEX,
+    LOCK_EXPIRIES, USER_REWARDS, USER_STAKING,
 };
 
 use cosmwasm_std::testing::{mock_env, mock_info};
-use cosmwasm_std::{from_json, Coin, Decimal, Uint128};
+use cosmwasm_std::{
+    attr, from_json, to_json_binary, Addr, BankMsg, Binary, Coin, CosmosMsg, Decimal, OwnedDeps,
+    StdError, Storage, Uint128, WasmMsg,
+};
+use cw2::set_contract_version;
+use cw20::{BalanceResponse, Cw20ExecuteMsg, Cw20ReceiveMsg, Expiration, TokenInfoResponse};
+use cw721::{Cw721ExecuteMsg, Cw721ReceiveMsg, NumTokensResponse, OwnerOfResponse};
+use cw_utils::PaymentError;
 
 #[test]
 fn proper_initialization() {
@@ -16,8 +31,21 @@ fn proper_initialization() {
 
     let msg = InstantiateMsg {
         kart_denom: "kart0000".to_string(),
+        kart_token_type: TokenType::Native,
         unlock_time: 10000,
         distribution_schedule: vec![],
+        drawdown_epoch: 86400,
+        max_drawdown: Decimal::percent(10),
+        receipt_name: "Staked KART".to_string(),
+        receipt_symbol: "sKART".to_string(),
+        receipt_decimals: 6,
+        position_nft: None,
+        boost_collections: vec![],
+        lock_tiers: vec![],
+        instant_unstake_penalty: Decimal::zero(),
+        penalty_treasury: None,
+        penalty_decay: 0,
+        pauser: None,
     };
 
     let info = mock_info("addr0000", &[]);
@@ -33,8 +61,16 @@ fn proper_initialization() {
         ConfigResponse {
             owner: "addr0000".to_string(),
             kart_denom: "kart0000".to_string(),
+            kart_token_type: TokenType::Native,
             unlock_time: 10000,
-            distribution_schedule: vec![],
+            drawdown_epoch: 86400,
+            max_drawdown: Decimal::percent(10),
+            position_nft: false,
+            boost_collections: vec![],
+            lock_tiers: vec![],
+            instant_unstake_penalty: Decimal::zero(),
+            penalty_treasury: None,
+            penalty_decay: 0,
         }
     );
 
@@ -50,9 +86,19 @@ fn proper_initialization() {
         StateResponse {
             total_staker: 0,
             total_stake_amount: Uint128::zero(),
+            total_share: Uint128::zero(),
+            total_weight: Uint128::zero(),
             last_distributed: 0,
             reward_index: HashMap::new(),
             reward_distributed: HashMap::new(),
+            epoch_start: 0,
+            epoch_bankroll: Uint128::zero(),
+            epoch_drawdown: Uint128::zero(),
+            next_unlock_id: 0,
+            total_unlock: Uint128::zero(),
+            total_unlock_share: Uint128::zero(),
+            reward_paid: HashMap::new(),
+            undistributed: HashMap::new(),
         }
     );
 }
@@ -63,21 +109,38 @@ fn test_stake_tokens() {
 
     let msg = InstantiateMsg {
         kart_denom: "kart0000".to_string(),
+        kart_token_type: TokenType::Native,
         unlock_time: 10000,
         distribution_schedule: vec![
             (
                 mock_env().block.time.seconds(),
                 mock_env().block.time.seconds() + 100,
                 Uint128::from(1000000u128),
-                "kart0000".to_string(),
+                AssetInfo::Native {
+                    denom: "kart0000".to_string(),
+                },
             ),
             (
                 mock_env().block.time.seconds() + 100,
                 mock_env().block.time.seconds() + 200,
                 Uint128::from(1000000u128),
-                "usk0000".to_string(),
+                AssetInfo::Native {
+                    denom: "usk0000".to_string(),
+                },
             ),
         ],
+        drawdown_epoch: 86400,
+        max_drawdown: Decimal::percent(10),
+        receipt_name: "Staked KART".to_string(),
+        receipt_symbol: "sKART".to_string(),
+        receipt_decimals: 6,
+        position_nft: None,
+        boost_collections: vec![],
+        lock_tiers: vec![],
+        instant_unstake_penalty: Decimal::zero(),
+        penalty_treasury: None,
+        penalty_decay: 0,
+        pauser: None,
     };
 
     let info = mock_info("addr0000", &[]);
@@ -89,7 +152,7 @@ fn test_stake_tokens() {
         deps.as_mut(),
         env.clone(),
         info.clone(),
-        ExecuteMsg::Stake {},
+        ExecuteMsg::Stake { lock_tier: None },
     )
     .unwrap();
 
@@ -108,6 +171,12 @@ fn test_stake_tokens() {
         .unwrap(),
         StakerInfoResponse {
             stake_amount: Uint128::from(100u128),
+            share: Uint128::from(100u128),
+            weight: Uint128::from(100u128),
+            boost: None,
+            locks: vec![],
+            auto_compound: false,
+            staked_at: mock_env().block.time.seconds(),
             pending_reward: HashMap::new(),
             reward_index: HashMap::new(),
             reward_claimed: HashMap::new(),
@@ -128,15 +197,34 @@ fn test_stake_tokens() {
         StateResponse {
             total_staker: 1,
             total_stake_amount: Uint128::from(100u128),
+            total_share: Uint128::from(100u128),
+            total_weight: Uint128::from(100u128),
             last_distributed: mock_env().block.time.seconds(),
             reward_index: HashMap::new(),
-            reward_distributed: HashMap::new(),
+            reward_distributed: HashMap::from([
+                ("kart0000".to_string(), Uint128::from(1000000u128)),
+                ("usk0000".to_string(), Uint128::from(1000000u128)),
+            ]),
+            epoch_start: 0,
+            epoch_bankroll: Uint128::zero(),
+            epoch_drawdown: Uint128::zero(),
+            next_unlock_id: 0,
+            total_unlock: Uint128::zero(),
+            total_unlock_share: Uint128::zero(),
+            reward_paid: HashMap::new(),
+            undistributed: HashMap::new(),
         }
     );
 
     env.block.time = env.block.time.plus_seconds(150);
 
-    let _res = execute(deps.as_mut(), env, info, ExecuteMsg::Stake {}).unwrap();
+    let _res = execute(
+        deps.as_mut(),
+        env,
+        info,
+        ExecuteMsg::Stake { lock_tier: None },
+    )
+    .unwrap();
 
     assert_eq!(
         from_json::<StakerInfoResponse>(
@@ -153,6 +241,12 @@ fn test_stake_tokens() {
         .unwrap(),
         StakerInfoResponse {
             stake_amount: Uint128::from(200u128),
+            share: Uint128::from(200u128),
+            weight: Uint128::from(200u128),
+            boost: None,
+            locks: vec![],
+            auto_compound: false,
+            staked_at: mock_env().block.time.seconds() + 75,
             pending_reward: HashMap::from([
                 ("kart0000".to_string(), Uint128::from(1000000u128)),
                 ("usk0000".to_string(), Uint128::from(500000u128))
@@ -185,6 +279,8 @@ fn test_stake_tokens() {
         StateResponse {
             total_staker: 1,
             total_stake_amount: Uint128::from(200u128),
+            total_share: Uint128::from(200u128),
+            total_weight: Uint128::from(200u128),
             last_distributed: mock_env().block.time.seconds() + 150,
             reward_index: HashMap::from([
                 (
@@ -196,7 +292,18 @@ fn test_stake_tokens() {
                     Decimal::from_ratio(500000u128, 100u128)
                 )
             ]),
-            reward_distributed: HashMap::new(),
+            reward_distributed: HashMap::from([
+                ("kart0000".to_string(), Uint128::from(1000000u128)),
+                ("usk0000".to_string(), Uint128::from(1000000u128)),
+            ]),
+            epoch_start: 0,
+            epoch_bankroll: Uint128::zero(),
+            epoch_drawdown: Uint128::zero(),
+            next_unlock_id: 0,
+            total_unlock: Uint128::zero(),
+            total_unlock_share: Uint128::zero(),
+            reward_paid: HashMap::new(),
+            undistributed: HashMap::new(),
         }
     );
 }
@@ -207,15 +314,34 @@ fn test_unstake() {
 
     let msg = InstantiateMsg {
         kart_denom: "kart0000".to_string(),
+        kart_token_type: TokenType::Native,
         unlock_time: 10000,
         distribution_schedule: vec![],
+        drawdown_epoch: 86400,
+        max_drawdown: Decimal::percent(10),
+        receipt_name: "Staked KART".to_string(),
+        receipt_symbol: "sKART".to_string(),
+        receipt_decimals: 6,
+        position_nft: None,
+        boost_collections: vec![],
+        lock_tiers: vec![],
+        instant_unstake_penalty: Decimal::zero(),
+        penalty_treasury: None,
+        penalty_decay: 0,
+        pauser: None,
     };
 
     let info = mock_info("addr0000", &[]);
     let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
 
     let info = mock_info("addr0000", &[Coin::new(100, "kart0000".to_string())]);
-    let _res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Stake {}).unwrap();
+    let _res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::Stake { lock_tier: None },
+    )
+    .unwrap();
 
     // unbond 150 tokens; failed
     let msg = ExecuteMsg::Unstake {
@@ -254,11 +380,19 @@ fn test_unstake() {
         .unwrap(),
         StakerInfoResponse {
             stake_amount: Uint128::zero(),
+            share: Uint128::zero(),
+            weight: Uint128::zero(),
+            boost: None,
+            locks: vec![],
+            auto_compound: false,
+            staked_at: mock_env().block.time.seconds(),
             pending_reward: HashMap::new(),
             reward_index: HashMap::new(),
             reward_claimed: HashMap::new(),
             unlock: Some(vec![Unlock {
+                id: 0,
                 amount: Uint128::from(100u128),
+                share: Uint128::from(100u128),
                 at: env.block.time.seconds() + 10000
             }]),
         }
@@ -271,21 +405,38 @@ fn test_withdraw() {
 
     let msg = InstantiateMsg {
         kart_denom: "kart0000".to_string(),
+        kart_token_type: TokenType::Native,
         unlock_time: 10000,
         distribution_schedule: vec![
             (
                 mock_env().block.time.seconds(),
                 mock_env().block.time.seconds() + 100,
                 Uint128::from(1000000u128),
-                "kart0000".to_string(),
+                AssetInfo::Native {
+                    denom: "kart0000".to_string(),
+                },
             ),
             (
                 mock_env().block.time.seconds() + 100,
                 mock_env().block.time.seconds() + 200,
                 Uint128::from(1000000u128),
-                "usk0000".to_string(),
+                AssetInfo::Native {
+                    denom: "usk0000".to_string(),
+                },
             ),
         ],
+        drawdown_epoch: 86400,
+        max_drawdown: Decimal::percent(10),
+        receipt_name: "Staked KART".to_string(),
+        receipt_symbol: "sKART".to_string(),
+        receipt_decimals: 6,
+        position_nft: None,
+        boost_collections: vec![],
+        lock_tiers: vec![],
+        instant_unstake_penalty: Decimal::zero(),
+        penalty_treasury: None,
+        penalty_decay: 0,
+        pauser: None,
     };
 
     let info = mock_info("addr0000", &[]);
@@ -295,7 +446,13 @@ fn test_withdraw() {
 
     let info = mock_info("addr0000", &[Coin::new(100, "kart0000".to_string())]);
     let mut env = mock_env();
-    let _res = execute(deps.as_mut(), env.clone(), info, ExecuteMsg::Stake {}).unwrap();
+    let _res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        ExecuteMsg::Stake { lock_tier: None },
+    )
+    .unwrap();
 
     // 100 seconds passed
     // 1,000,000 kart0000 rewards distributed
@@ -324,6 +481,12 @@ fn test_withdraw() {
         .unwrap(),
         StakerInfoResponse {
             stake_amount: Uint128::zero(),
+            share: Uint128::zero(),
+            weight: Uint128::zero(),
+            boost: None,
+            locks: vec![],
+            auto_compound: false,
+            staked_at: mock_env().block.time.seconds(),
             pending_reward: HashMap::from([
                 ("kart0000".to_string(), Uint128::from(1000000u128)),
                 ("usk0000".to_string(), Uint128::from(500000u128))
@@ -340,7 +503,9 @@ fn test_withdraw() {
             ]),
             reward_claimed: HashMap::new(),
             unlock: Some(vec![Unlock {
+                id: 0,
                 amount: Uint128::from(100u128),
+                share: Uint128::from(100u128),
                 at: env.clone().block.time.seconds() + 10000
             }]),
         }
@@ -366,6 +531,12 @@ fn test_withdraw() {
         .unwrap(),
         StakerInfoResponse {
             stake_amount: Uint128::zero(),
+            share: Uint128::zero(),
+            weight: Uint128::zero(),
+            boost: None,
+            locks: vec![],
+            auto_compound: false,
+            staked_at: 0,
             pending_reward: HashMap::new(),
             reward_index: HashMap::new(),
             reward_claimed: HashMap::new(),
@@ -373,3 +544,4158 @@ fn test_withdraw() {
         }
     );
 }
+
+#[test]
+fn test_stake_cw20() {
+    let mut deps = mock_dependencies(&[]);
+
+    let msg = InstantiateMsg {
+        kart_denom: "kart0000".to_string(),
+        kart_token_type: TokenType::Cw20,
+        unlock_time: 10000,
+        distribution_schedule: vec![],
+        drawdown_epoch: 86400,
+        max_drawdown: Decimal::percent(10),
+        receipt_name: "Staked KART".to_string(),
+        receipt_symbol: "sKART".to_string(),
+        receipt_decimals: 6,
+        position_nft: None,
+        boost_collections: vec![],
+        lock_tiers: vec![],
+        instant_unstake_penalty: Decimal::zero(),
+        penalty_treasury: None,
+        penalty_decay: 0,
+        pauser: None,
+    };
+
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    // native funds are rejected when KART is a CW20
+    let info = mock_info("addr0000", &[Coin::new(100, "kart0000".to_string())]);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::Stake { lock_tier: None },
+    )
+    .unwrap_err();
+    assert_eq!(res, ContractError::UnsupportedToken {});
+
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "addr0000".to_string(),
+        amount: Uint128::from(100u128),
+        msg: to_json_binary(&Cw20HookMsg::Stake { lock_tier: None }).unwrap(),
+    });
+
+    // only the KART token contract can call the hook
+    let info = mock_info("fake0000", &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap_err();
+    assert_eq!(res, ContractError::UnsupportedToken {});
+
+    let info = mock_info("kart0000", &[]);
+    let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    assert_eq!(
+        from_json::<StakerInfoResponse>(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::StakerInfo {
+                    staker: "addr0000".to_string(),
+                    block_time: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap()
+        .stake_amount,
+        Uint128::from(100u128)
+    );
+
+    let info = mock_info("addr0000", &[]);
+    let mut env = mock_env();
+    let _res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info.clone(),
+        ExecuteMsg::Unstake {
+            amount: Uint128::from(100u128),
+        },
+    )
+    .unwrap();
+
+    env.block.time = env.block.time.plus_seconds(10000);
+    let res = execute(deps.as_mut(), env, info, ExecuteMsg::Withdraw { id: 0 }).unwrap();
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "kart0000".to_string(),
+            msg: to_json_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: "addr0000".to_string(),
+                amount: Uint128::from(100u128),
+            })
+            .unwrap(),
+            funds: vec![],
+        })
+    );
+}
+
+#[test]
+fn test_claim_cw20_reward() {
+    let mut deps = mock_dependencies(&[]);
+
+    let msg = InstantiateMsg {
+        kart_denom: "kart0000".to_string(),
+        kart_token_type: TokenType::Native,
+        unlock_time: 10000,
+        distribution_schedule: vec![],
+        drawdown_epoch: 86400,
+        max_drawdown: Decimal::percent(10),
+        receipt_name: "Staked KART".to_string(),
+        receipt_symbol: "sKART".to_string(),
+        receipt_decimals: 6,
+        position_nft: None,
+        boost_collections: vec![],
+        lock_tiers: vec![],
+        instant_unstake_penalty: Decimal::zero(),
+        penalty_treasury: None,
+        penalty_decay: 0,
+        pauser: None,
+    };
+
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+    let _res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[Coin::new(1000000, "usk0000".to_string())]),
+        ExecuteMsg::SetDistribution {
+            reward_asset: AssetInfo::Native {
+                denom: "usk0000".to_string(),
+            },
+            start_date: mock_env().block.time.seconds(),
+            end_date: mock_env().block.time.seconds() + 100,
+            amount: Uint128::from(1000000u128),
+            curve: None,
+        },
+    )
+    .unwrap();
+
+    // cw20 rewards are funded through the token contract
+    let _res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("token0000", &[]),
+        ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "addr0000".to_string(),
+            amount: Uint128::from(1000000u128),
+            msg: to_json_binary(&Cw20HookMsg::SetDistribution {
+                start_date: mock_env().block.time.seconds(),
+                end_date: mock_env().block.time.seconds() + 100,
+                curve: None,
+            })
+            .unwrap(),
+        }),
+    )
+    .unwrap();
+
+    let info = mock_info("addr0000", &[Coin::new(100, "kart0000".to_string())]);
+    let mut env = mock_env();
+    let _res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        ExecuteMsg::Stake { lock_tier: None },
+    )
+    .unwrap();
+
+    env.block.time = env.block.time.plus_seconds(100);
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), env, info, ExecuteMsg::Claim {}).unwrap();
+
+    assert_eq!(
+        res.messages
+            .into_iter()
+            .map(|m| m.msg)
+            .collect::<Vec<CosmosMsg>>(),
+        vec![
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "addr0000".to_string(),
+                amount: vec![Coin::new(1000000, "usk0000".to_string())],
+            }),
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "token0000".to_string(),
+                msg: to_json_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: "addr0000".to_string(),
+                    amount: Uint128::from(1000000u128),
+                })
+                .unwrap(),
+                funds: vec![],
+            }),
+        ]
+    );
+
+    let staker_info = from_json::<StakerInfoResponse>(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::StakerInfo {
+                staker: "addr0000".to_string(),
+                block_time: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        staker_info.reward_claimed,
+        HashMap::from([
+            ("usk0000".to_string(), Uint128::from(1000000u128)),
+            ("cw20:token0000".to_string(), Uint128::from(1000000u128)),
+        ])
+    );
+}
+
+#[test]
+fn test_deposit_revenue() {
+    let mut deps = mock_dependencies(&[]);
+
+    let msg = InstantiateMsg {
+        kart_denom: "kart0000".to_string(),
+        kart_token_type: TokenType::Native,
+        unlock_time: 10000,
+        distribution_schedule: vec![],
+        drawdown_epoch: 86400,
+        max_drawdown: Decimal::percent(10),
+        receipt_name: "Staked KART".to_string(),
+        receipt_symbol: "sKART".to_string(),
+        receipt_decimals: 6,
+        position_nft: None,
+        boost_collections: vec![],
+        lock_tiers: vec![],
+        instant_unstake_penalty: Decimal::zero(),
+        penalty_treasury: None,
+        penalty_decay: 0,
+        pauser: None,
+    };
+
+    let info = mock_info("owner0000", &[]);
+    let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+    let msg = ExecuteMsg::AddGame {
+        game: "game0000".to_string(),
+        max_exposure: Uint128::from(1000u128),
+    };
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        msg.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+    let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let info = mock_info("addr0000", &[Coin::new(100, "kart0000".to_string())]);
+    let _res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::Stake { lock_tier: None },
+    )
+    .unwrap();
+    let info = mock_info("addr0001", &[Coin::new(300, "kart0000".to_string())]);
+    let _res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::Stake { lock_tier: None },
+    )
+    .unwrap();
+
+    // unregistered contracts cannot deposit
+    let info = mock_info("fake0000", &[Coin::new(1000, "usk0000".to_string())]);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::DepositRevenue {},
+    )
+    .unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    let info = mock_info("game0000", &[Coin::new(1000, "usk0000".to_string())]);
+    let _res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::DepositRevenue {},
+    )
+    .unwrap();
+
+    // cw20 revenue is sent by the game through the token contract, a stake pause does not block it
+    let info = mock_info("owner0000", &[]);
+    let _res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::SetPaused {
+            paused: PauseFlags {
+                stake: true,
+                ..PauseFlags::default()
+            },
+        },
+    )
+    .unwrap();
+
+    let info = mock_info("token0000", &[]);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "fake0000".to_string(),
+            amount: Uint128::from(400u128),
+            msg: to_json_binary(&Cw20HookMsg::DepositRevenue {}).unwrap(),
+        }),
+    )
+    .unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    let _res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "game0000".to_string(),
+            amount: Uint128::from(400u128),
+            msg: to_json_binary(&Cw20HookMsg::DepositRevenue {}).unwrap(),
+        }),
+    )
+    .unwrap();
+
+    let staker_info = from_json::<StakerInfoResponse>(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::StakerInfo {
+                staker: "addr0001".to_string(),
+                block_time: Some(mock_env().block.time.seconds()),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        staker_info.pending_reward,
+        HashMap::from([
+            ("usk0000".to_string(), Uint128::from(750u128)),
+            ("cw20:token0000".to_string(), Uint128::from(300u128)),
+        ])
+    );
+
+    assert_eq!(
+        from_json::<GameResponse>(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::Game {
+                    game: "game0000".to_string(),
+                },
+            )
+            .unwrap()
+        )
+        .unwrap(),
+        GameResponse {
+            game: "game0000".to_string(),
+            revenue: HashMap::from([
+                ("usk0000".to_string(), Uint128::from(1000u128)),
+                ("cw20:token0000".to_string(), Uint128::from(400u128)),
+            ]),
+            max_exposure: Uint128::from(1000u128),
+            total_payout: Uint128::zero(),
+            epoch_payout: Uint128::zero(),
+        }
+    );
+}
+
+#[test]
+fn test_payout() {
+    let mut deps = mock_dependencies(&[]);
+
+    let mut msg = InstantiateMsg {
+        kart_denom: "kart0000".to_string(),
+        kart_token_type: TokenType::Native,
+        unlock_time: 10000,
+        distribution_schedule: vec![],
+        drawdown_epoch: 86400,
+        max_drawdown: Decimal::percent(10),
+        receipt_name: "Staked KART".to_string(),
+        receipt_symbol: "sKART".to_string(),
+        receipt_decimals: 6,
+        position_nft: None,
+        boost_collections: vec![],
+        lock_tiers: vec![],
+        instant_unstake_penalty: Decimal::zero(),
+        penalty_treasury: None,
+        penalty_decay: 0,
+        pauser: None,
+    };
+
+    let info = mock_info("owner0000", &[]);
+    msg.drawdown_epoch = 0;
+    let res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg.clone()).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            msg: "drawdown epoch must not be zero".to_string()
+        }
+    );
+    msg.drawdown_epoch = 86400;
+    let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+    let msg = ExecuteMsg::AddGame {
+        game: "game0000".to_string(),
+        max_exposure: Uint128::from(50u128),
+    };
+    let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let info = mock_info("addr0000", &[Coin::new(100, "kart0000".to_string())]);
+    let _res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::Stake { lock_tier: None },
+    )
+    .unwrap();
+    let info = mock_info("addr0001", &[Coin::new(300, "kart0000".to_string())]);
+    let _res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::Stake { lock_tier: None },
+    )
+    .unwrap();
+
+    let info = mock_info("addr0000", &[]);
+    let _res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::Unstake {
+            amount: Uint128::from(20u128),
+        },
+    )
+    .unwrap();
+
+    // 10% of the 400 KART bankroll, bonded and unbonding, can be drawn in this epoch
+    let info = mock_info("game0000", &[]);
+    let msg = ExecuteMsg::Payout {
+        recipient: "winner0000".to_string(),
+        amount: Uint128::from(41u128),
+    };
+    let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+    assert_eq!(res, ContractError::DrawdownCapExceeded {});
+
+    let msg = ExecuteMsg::Payout {
+        recipient: "winner0000".to_string(),
+        amount: Uint128::from(40u128),
+    };
+    let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: "winner0000".to_string(),
+            amount: vec![Coin::new(40, "kart0000".to_string())],
+        })
+    );
+
+    // the loss is shared pro rata, the pending unlock takes its part too
+    let staker_info = from_json::<StakerInfoResponse>(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::StakerInfo {
+                staker: "addr0000".to_string(),
+                block_time: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(staker_info.stake_amount, Uint128::from(72u128));
+    assert_eq!(staker_info.share, Uint128::from(80u128));
+    assert_eq!(
+        staker_info.unlock,
+        Some(vec![Unlock {
+            id: 0,
+            amount: Uint128::from(18u128),
+            share: Uint128::from(20u128),
+            at: mock_env().block.time.seconds() + 10000,
+        }])
+    );
+
+    let staker_info = from_json::<StakerInfoResponse>(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::StakerInfo {
+                staker: "addr0001".to_string(),
+                block_time: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(staker_info.stake_amount, Uint128::from(270u128));
+
+    // the next epoch opens a new drawdown window and resets the game exposure
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(86400);
+    let msg = ExecuteMsg::Payout {
+        recipient: "winner0000".to_string(),
+        amount: Uint128::from(13u128),
+    };
+    let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+    let msg = ExecuteMsg::Payout {
+        recipient: "winner0000".to_string(),
+        amount: Uint128::from(38u128),
+    };
+    let res = execute(deps.as_mut(), env, info, msg).unwrap_err();
+    assert_eq!(res, ContractError::ExposureLimitExceeded {});
+
+    let game = from_json::<GameResponse>(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Game {
+                game: "game0000".to_string(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(game.total_payout, Uint128::from(53u128));
+    assert_eq!(game.epoch_payout, Uint128::from(13u128));
+
+    // stakers can exit with their reduced stake
+    let info = mock_info("addr0001", &[]);
+    let _res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::Unstake {
+            amount: Uint128::from(260u128),
+        },
+    )
+    .unwrap();
+
+    let state = from_json::<StateResponse>(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::State { block_time: None },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(state.total_staker, 1);
+    assert_eq!(state.total_stake_amount, Uint128::from(70u128));
+    assert_eq!(state.total_share, Uint128::from(80u128));
+    assert_eq!(state.total_unlock, Uint128::from(277u128));
+
+    // the unlock of addr0000 lost 3 KART over both payouts
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(10000);
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), env, info, ExecuteMsg::Withdraw { id: 0 }).unwrap();
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: "addr0000".to_string(),
+            amount: vec![Coin::new(17, "kart0000".to_string())],
+        })
+    );
+}
+
+#[test]
+fn test_receipt_transfer() {
+    let mut deps = mock_dependencies(&[]);
+
+    let msg = InstantiateMsg {
+        kart_denom: "kart0000".to_string(),
+        kart_token_type: TokenType::Native,
+        unlock_time: 10000,
+        distribution_schedule: vec![(
+            mock_env().block.time.seconds(),
+            mock_env().block.time.seconds() + 100,
+            Uint128::from(1000000u128),
+            AssetInfo::Native {
+                denom: "usk0000".to_string(),
+            },
+        )],
+        drawdown_epoch: 86400,
+        max_drawdown: Decimal::percent(10),
+        receipt_name: "Staked KART".to_string(),
+        receipt_symbol: "sKART".to_string(),
+        receipt_decimals: 6,
+        position_nft: None,
+        boost_collections: vec![],
+        lock_tiers: vec![],
+        instant_unstake_penalty: Decimal::zero(),
+        penalty_treasury: None,
+        penalty_decay: 0,
+        pauser: None,
+    };
+
+    let info = mock_info("owner0000", &[]);
+    let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let info = mock_info("addr0000", &[Coin::new(100, "kart0000".to_string())]);
+    let mut env = mock_env();
+    let _res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        ExecuteMsg::Stake { lock_tier: None },
+    )
+    .unwrap();
+
+    let balance = from_json::<BalanceResponse>(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Balance {
+                address: "addr0000".to_string(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(balance.balance, Uint128::from(100u128));
+
+    // half of the receipt changes hands after 50 seconds
+    env.block.time = env.block.time.plus_seconds(50);
+    let info = mock_info("addr0000", &[]);
+    let msg = ExecuteMsg::Transfer {
+        recipient: "addr0001".to_string(),
+        amount: Uint128::from(50u128),
+    };
+    let _res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+    env.block.time = env.block.time.plus_seconds(50);
+    for (staker, stake_amount, pending_reward) in [
+        ("addr0000", 50u128, 750000u128),
+        ("addr0001", 50u128, 250000u128),
+    ] {
+        let staker_info = from_json::<StakerInfoResponse>(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::StakerInfo {
+                    staker: staker.to_string(),
+                    block_time: Some(env.block.time.seconds()),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(staker_info.stake_amount, Uint128::from(stake_amount));
+        assert_eq!(
+            staker_info.pending_reward,
+            HashMap::from([("usk0000".to_string(), Uint128::from(pending_reward))])
+        );
+    }
+
+    // the new holder can unstake, which burns the receipt
+    let info = mock_info("addr0001", &[]);
+    let msg = ExecuteMsg::Unstake {
+        amount: Uint128::from(50u128),
+    };
+    let _res = execute(deps.as_mut(), env, info, msg).unwrap();
+
+    let token_info = from_json::<TokenInfoResponse>(
+        &query(deps.as_ref(), mock_env(), QueryMsg::TokenInfo {}).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(token_info.symbol, "sKART".to_string());
+    assert_eq!(token_info.total_supply, Uint128::from(50u128));
+
+    let state = from_json::<StateResponse>(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::State { block_time: None },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(state.total_staker, 1);
+    assert_eq!(state.total_share, Uint128::from(50u128));
+}
+
+#[test]
+fn test_stake_position_nft() {
+    let mut deps = mock_dependencies(&[]);
+
+    let msg = InstantiateMsg {
+        kart_denom: "kart0000".to_string(),
+        kart_token_type: TokenType::Native,
+        unlock_time: 10000,
+        distribution_schedule: vec![(
+            mock_env().block.time.seconds(),
+            mock_env().block.time.seconds() + 100,
+            Uint128::from(1000000u128),
+            AssetInfo::Native {
+                denom: "usk0000".to_string(),
+            },
+        )],
+        drawdown_epoch: 86400,
+        max_drawdown: Decimal::percent(10),
+        receipt_name: "Staked KART".to_string(),
+        receipt_symbol: "sKART".to_string(),
+        receipt_decimals: 6,
+        position_nft: Some(PositionNftInfo {
+            name: "KART Position".to_string(),
+            symbol: "KARTPOS".to_string(),
+        }),
+        boost_collections: vec![("nft0000".to_string(), Decimal::percent(150))],
+        lock_tiers: vec![],
+        instant_unstake_penalty: Decimal::zero(),
+        penalty_treasury: None,
+        penalty_decay: 0,
+        pauser: None,
+    };
+
+    let info = mock_info("owner0000", &[]);
+    let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let info = mock_info("addr0000", &[Coin::new(100, "kart0000".to_string())]);
+    let mut env = mock_env();
+    let _res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        ExecuteMsg::Stake { lock_tier: None },
+    )
+    .unwrap();
+
+    // the stake lives in the position, not in the wallet
+    let staker_info = from_json::<StakerInfoResponse>(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::StakerInfo {
+                staker: "addr0000".to_string(),
+                block_time: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(staker_info.stake_amount, Uint128::zero());
+
+    // a boost would land on the empty wallet record instead of the position
+    let msg = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+        sender: "addr0000".to_string(),
+        token_id: "7".to_string(),
+        msg: Binary::default(),
+    });
+    let res = execute(deps.as_mut(), env.clone(), mock_info("nft0000", &[]), msg).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            msg: "boost NFTs are not supported with position NFTs".to_string()
+        }
+    );
+
+    env.block.time = env.block.time.plus_seconds(50);
+    let info = mock_info("addr0000", &[]);
+    let msg = ExecuteMsg::TransferNft {
+        recipient: "addr0001".to_string(),
+        token_id: "1".to_string(),
+    };
+    let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+    let owner = from_json::<OwnerOfResponse>(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::OwnerOf {
+                token_id: "1".to_string(),
+                include_expired: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(owner.owner, "addr0001".to_string());
+
+    // the previous owner lost control of the position
+    let msg = ExecuteMsg::UnstakePosition {
+        token_id: "1".to_string(),
+        amount: Uint128::from(100u128),
+    };
+    let res = execute(deps.as_mut(), env.clone(), info, msg.clone()).unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    let info = mock_info("addr0001", &[]);
+    let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+    let position = from_json::<StakerInfoResponse>(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Position {
+                token_id: "1".to_string(),
+                block_time: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        position.pending_reward,
+        HashMap::from([("usk0000".to_string(), Uint128::from(500000u128))])
+    );
+
+    // withdrawing the last unlock pays the new owner and burns the position
+    env.block.time = env.block.time.plus_seconds(10000);
+    let msg = ExecuteMsg::WithdrawPosition {
+        token_id: "1".to_string(),
+        id: 0,
+    };
+    let res = execute(deps.as_mut(), env, info, msg).unwrap();
+    assert_eq!(
+        res.messages
+            .into_iter()
+            .map(|m| m.msg)
+            .collect::<Vec<CosmosMsg>>(),
+        vec![
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "addr0001".to_string(),
+                amount: vec![Coin::new(100, "kart0000".to_string())],
+            }),
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "addr0001".to_string(),
+                amount: vec![Coin::new(500000, "usk0000".to_string())],
+            }),
+        ]
+    );
+
+    let num_tokens = from_json::<NumTokensResponse>(
+        &query(deps.as_ref(), mock_env(), QueryMsg::NumTokens {}).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(num_tokens.count, 0);
+}
+
+#[test]
+fn test_nft_boost() {
+    let mut deps = mock_dependencies(&[]);
+
+    let msg = InstantiateMsg {
+        kart_denom: "kart0000".to_string(),
+        kart_token_type: TokenType::Native,
+        unlock_time: 10000,
+        distribution_schedule: vec![(
+            mock_env().block.time.seconds(),
+            mock_env().block.time.seconds() + 100,
+            Uint128::from(1200000u128),
+            AssetInfo::Native {
+                denom: "usk0000".to_string(),
+            },
+        )],
+        drawdown_epoch: 86400,
+        max_drawdown: Decimal::percent(10),
+        receipt_name: "Staked KART".to_string(),
+        receipt_symbol: "sKART".to_string(),
+        receipt_decimals: 6,
+        position_nft: None,
+        boost_collections: vec![("nft0000".to_string(), Decimal::percent(200))],
+        lock_tiers: vec![],
+        instant_unstake_penalty: Decimal::zero(),
+        penalty_treasury: None,
+        penalty_decay: 0,
+        pauser: None,
+    };
+
+    let info = mock_info("owner0000", &[]);
+    let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    for staker in ["addr0000", "addr0001"] {
+        let info = mock_info(staker, &[Coin::new(100, "kart0000".to_string())]);
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::Stake { lock_tier: None },
+        )
+        .unwrap();
+    }
+
+    let msg = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+        sender: "addr0000".to_string(),
+        token_id: "7".to_string(),
+        msg: Binary::default(),
+    });
+    let info = mock_info("nft0001", &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap_err();
+    assert_eq!(res, ContractError::UnsupportedToken {});
+
+    let info = mock_info("nft0000", &[]);
+    let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let state = from_json::<StateResponse>(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::State { block_time: None },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(state.total_stake_amount, Uint128::from(200u128));
+    assert_eq!(state.total_weight, Uint128::from(300u128));
+
+    // the boost ends as soon as the NFT is withdrawn
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(50);
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), env.clone(), info, ExecuteMsg::WithdrawNft {}).unwrap();
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "nft0000".to_string(),
+            msg: to_json_binary(&Cw721ExecuteMsg::TransferNft {
+                recipient: "addr0000".to_string(),
+                token_id: "7".to_string(),
+            })
+            .unwrap(),
+            funds: vec![],
+        })
+    );
+
+    env.block.time = env.block.time.plus_seconds(50);
+    for (staker, pending_reward) in [("addr0000", 700000u128), ("addr0001", 500000u128)] {
+        let staker_info = from_json::<StakerInfoResponse>(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::StakerInfo {
+                    staker: staker.to_string(),
+                    block_time: Some(env.block.time.seconds()),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(staker_info.weight, Uint128::from(100u128));
+        assert_eq!(
+            staker_info.pending_reward,
+            HashMap::from([("usk0000".to_string(), Uint128::from(pending_reward))])
+        );
+    }
+}
+
+#[test]
+fn test_nft_boost_returned_on_exit() {
+    let mut deps = mock_dependencies(&[]);
+
+    let msg = InstantiateMsg {
+        kart_denom: "kart0000".to_string(),
+        kart_token_type: TokenType::Native,
+        unlock_time: 10000,
+        distribution_schedule: vec![],
+        drawdown_epoch: 86400,
+        max_drawdown: Decimal::percent(10),
+        receipt_name: "Staked KART".to_string(),
+        receipt_symbol: "sKART".to_string(),
+        receipt_decimals: 6,
+        position_nft: None,
+        boost_collections: vec![("nft0000".to_string(), Decimal::percent(200))],
+        lock_tiers: vec![],
+        instant_unstake_penalty: Decimal::zero(),
+        penalty_treasury: None,
+        penalty_decay: 0,
+        pauser: None,
+    };
+
+    let info = mock_info("owner0000", &[]);
+    let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    for (staker, token_id) in [("addr0000", "7"), ("addr0001", "8")] {
+        let info = mock_info(staker, &[Coin::new(100, "kart0000".to_string())]);
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::Stake { lock_tier: None },
+        )
+        .unwrap();
+
+        let info = mock_info("nft0000", &[]);
+        let msg = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: staker.to_string(),
+            token_id: token_id.to_string(),
+            msg: Binary::default(),
+        });
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    }
+
+    let return_msg = |recipient: &str, token_id: &str| {
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "nft0000".to_string(),
+            msg: to_json_binary(&Cw721ExecuteMsg::TransferNft {
+                recipient: recipient.to_string(),
+                token_id: token_id.to_string(),
+            })
+            .unwrap(),
+            funds: vec![],
+        })
+    };
+
+    // leaving through the unlock period
+    let info = mock_info("addr0000", &[]);
+    let _res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        ExecuteMsg::Unstake {
+            amount: Uint128::from(100u128),
+        },
+    )
+    .unwrap();
+
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(10000);
+    let res = execute(deps.as_mut(), env, info.clone(), ExecuteMsg::WithdrawAll {}).unwrap();
+    assert!(res
+        .messages
+        .iter()
+        .any(|m| m.msg == return_msg("addr0000", "7")));
+
+    let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::WithdrawNft {}).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            msg: "no boost NFT deposited".to_string(),
+        }
+    );
+
+    // leaving at once
+    let info = mock_info("addr0001", &[]);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::InstantUnstake {
+            amount: Uint128::from(100u128),
+        },
+    )
+    .unwrap();
+    assert!(res
+        .messages
+        .iter()
+        .any(|m| m.msg == return_msg("addr0001", "8")));
+}
+
+#[test]
+fn test_lock_tier() {
+    let mut deps = mock_dependencies(&[]);
+
+    let msg = InstantiateMsg {
+        kart_denom: "kart0000".to_string(),
+        kart_token_type: TokenType::Native,
+        unlock_time: 10000,
+        distribution_schedule: vec![(
+            mock_env().block.time.seconds(),
+            mock_env().block.time.seconds() + 100,
+            Uint128::from(1200000u128),
+            AssetInfo::Native {
+                denom: "usk0000".to_string(),
+            },
+        )],
+        drawdown_epoch: 86400,
+        max_drawdown: Decimal::percent(10),
+        receipt_name: "Staked KART".to_string(),
+        receipt_symbol: "sKART".to_string(),
+        receipt_decimals: 6,
+        position_nft: None,
+        boost_collections: vec![],
+        lock_tiers: vec![LockTier {
+            duration: 50,
+            multiplier: Decimal::percent(300),
+        }],
+        instant_unstake_penalty: Decimal::zero(),
+        penalty_treasury: None,
+        penalty_decay: 0,
+        pauser: None,
+    };
+
+    let info = mock_info("owner0000", &[]);
+    let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let info = mock_info("addr0000", &[Coin::new(100, "kart0000".to_string())]);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        ExecuteMsg::Stake { lock_tier: Some(1) },
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            msg: "invalid lock tier".to_string()
+        }
+    );
+    let _res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::Stake { lock_tier: Some(0) },
+    )
+    .unwrap();
+
+    let info = mock_info("addr0001", &[Coin::new(100, "kart0000".to_string())]);
+    let _res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::Stake { lock_tier: None },
+    )
+    .unwrap();
+
+    let state = from_json::<StateResponse>(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::State { block_time: None },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(state.total_weight, Uint128::from(400u128));
+
+    // locked stake can neither be unstaked nor transferred
+    let info = mock_info("addr0000", &[]);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        ExecuteMsg::Unstake {
+            amount: Uint128::from(10u128),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(res, ContractError::StakeLocked {});
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        ExecuteMsg::Transfer {
+            recipient: "addr0002".to_string(),
+            amount: Uint128::from(10u128),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(res, ContractError::StakeLocked {});
+
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(50);
+    let _res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        ExecuteMsg::Unstake {
+            amount: Uint128::from(100u128),
+        },
+    )
+    .unwrap();
+
+    env.block.time = env.block.time.plus_seconds(50);
+    for (staker, pending_reward) in [("addr0000", 450000u128), ("addr0001", 750000u128)] {
+        let staker_info = from_json::<StakerInfoResponse>(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::StakerInfo {
+                    staker: staker.to_string(),
+                    block_time: Some(env.block.time.seconds()),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert!(staker_info.locks.is_empty());
+        assert_eq!(
+            staker_info.pending_reward,
+            HashMap::from([("usk0000".to_string(), Uint128::from(pending_reward))])
+        );
+    }
+}
+
+#[test]
+fn test_lock_expires_mid_accrual() {
+    let mut deps = mock_dependencies(&[]);
+
+    let msg = InstantiateMsg {
+        kart_denom: "kart0000".to_string(),
+        kart_token_type: TokenType::Native,
+        unlock_time: 10000,
+        distribution_schedule: vec![(
+            mock_env().block.time.seconds(),
+            mock_env().block.time.seconds() + 100,
+            Uint128::from(1200000u128),
+            AssetInfo::Native {
+                denom: "usk0000".to_string(),
+            },
+        )],
+        drawdown_epoch: 86400,
+        max_drawdown: Decimal::percent(10),
+        receipt_name: "Staked KART".to_string(),
+        receipt_symbol: "sKART".to_string(),
+        receipt_decimals: 6,
+        position_nft: None,
+        boost_collections: vec![],
+        lock_tiers: vec![LockTier {
+            duration: 10,
+            multiplier: Decimal::percent(300),
+        }],
+        instant_unstake_penalty: Decimal::zero(),
+        penalty_treasury: None,
+        penalty_decay: 0,
+        pauser: None,
+    };
+
+    let info = mock_info("owner0000", &[]);
+    let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    for (staker, lock_tier) in [("addr0000", Some(0)), ("addr0001", None)] {
+        let info = mock_info(staker, &[Coin::new(100, "kart0000".to_string())]);
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::Stake { lock_tier },
+        )
+        .unwrap();
+    }
+
+    // 3:1 for the first 10 seconds, then 1:1 although nobody settled at the expiry
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(100);
+    let expected = [("addr0000", 630000u128), ("addr0001", 570000u128)];
+    for (staker, pending_reward) in expected {
+        let staker_info = from_json::<StakerInfoResponse>(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::StakerInfo {
+                    staker: staker.to_string(),
+                    block_time: Some(env.block.time.seconds()),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            staker_info.pending_reward,
+            HashMap::from([("usk0000".to_string(), Uint128::from(pending_reward))])
+        );
+    }
+
+    // once accrued, the locked staker settles against the stored expiry index
+    let info = mock_info("addr0001", &[]);
+    let _res = execute(deps.as_mut(), env.clone(), info, ExecuteMsg::Claim {}).unwrap();
+
+    let state = from_json::<StateResponse>(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::State { block_time: None },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(state.total_weight, Uint128::from(200u128));
+
+    let staker_info = from_json::<StakerInfoResponse>(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::StakerInfo {
+                staker: "addr0000".to_string(),
+                block_time: Some(env.block.time.seconds()),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert!(staker_info.locks.is_empty());
+    assert_eq!(staker_info.weight, Uint128::from(100u128));
+    assert_eq!(
+        staker_info.pending_reward,
+        HashMap::from([("usk0000".to_string(), Uint128::from(630000u128))])
+    );
+
+    // the expiry index is dropped with the last lock expiring at that time
+    let until = mock_env().block.time.seconds() + 10;
+    assert!(EXPIRY_INDEX
+        .may_load(deps.as_ref().storage, (until, "usk0000"))
+        .unwrap()
+        .is_some());
+    let info = mock_info("addr0000", &[]);
+    let _res = execute(deps.as_mut(), env, info, ExecuteMsg::Claim {}).unwrap();
+    assert!(EXPIRY_INDEX
+        .may_load(deps.as_ref().storage, (until, "usk0000"))
+        .unwrap()
+        .is_none());
+    assert!(LOCK_EXPIRIES
+        .may_load(deps.as_ref().storage, until)
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn test_compound() {
+    let mut deps = mock_dependencies(&[]);
+
+    let msg = InstantiateMsg {
+        kart_denom: "kart0000".to_string(),
+        kart_token_type: TokenType::Native,
+        unlock_time: 10000,
+        distribution_schedule: vec![(
+            mock_env().block.time.seconds(),
+            mock_env().block.time.seconds() + 100,
+            Uint128::from(300u128),
+            AssetInfo::Native {
+                denom: "kart0000".to_string(),
+            },
+        )],
+        drawdown_epoch: 86400,
+        max_drawdown: Decimal::percent(10),
+        receipt_name: "Staked KART".to_string(),
+        receipt_symbol: "sKART".to_string(),
+        receipt_decimals: 6,
+        position_nft: None,
+        boost_collections: vec![],
+        lock_tiers: vec![],
+        instant_unstake_penalty: Decimal::zero(),
+        penalty_treasury: None,
+        penalty_decay: 0,
+        pauser: None,
+    };
+
+    let info = mock_info("owner0000", &[]);
+    let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let info = mock_info("addr0000", &[Coin::new(100, "kart0000".to_string())]);
+    let _res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::Stake { lock_tier: None },
+    )
+    .unwrap();
+
+    let info = mock_info("addr0000", &[]);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        ExecuteMsg::Compound {},
+    )
+    .unwrap_err();
+    assert_eq!(res, ContractError::InvalidAmount {});
+
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(50);
+    let _res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info.clone(),
+        ExecuteMsg::Compound {},
+    )
+    .unwrap();
+
+    let balance = from_json::<BalanceResponse>(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Balance {
+                address: "addr0000".to_string(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(balance.balance, Uint128::from(250u128));
+
+    // with auto-compound on, claiming restakes the KART reward instead of paying it
+    let _res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info.clone(),
+        ExecuteMsg::SetAutoCompound { enabled: true },
+    )
+    .unwrap();
+
+    env.block.time = env.block.time.plus_seconds(50);
+    let res = execute(deps.as_mut(), env.clone(), info, ExecuteMsg::Claim {}).unwrap();
+    assert!(res.messages.is_empty());
+
+    let staker_info = from_json::<StakerInfoResponse>(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::StakerInfo {
+                staker: "addr0000".to_string(),
+                block_time: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert!(staker_info.auto_compound);
+    assert_eq!(staker_info.stake_amount, Uint128::from(400u128));
+    assert_eq!(staker_info.share, Uint128::from(400u128));
+    assert_eq!(
+        staker_info.reward_claimed,
+        HashMap::from([("kart0000".to_string(), Uint128::from(300u128))])
+    );
+}
+
+#[test]
+fn test_rebond() {
+    let mut deps = mock_dependencies(&[]);
+
+    let msg = InstantiateMsg {
+        kart_denom: "kart0000".to_string(),
+        kart_token_type: TokenType::Native,
+        unlock_time: 10000,
+        distribution_schedule: vec![],
+        drawdown_epoch: 86400,
+        max_drawdown: Decimal::percent(10),
+        receipt_name: "Staked KART".to_string(),
+        receipt_symbol: "sKART".to_string(),
+        receipt_decimals: 6,
+        position_nft: None,
+        boost_collections: vec![],
+        lock_tiers: vec![],
+        instant_unstake_penalty: Decimal::zero(),
+        penalty_treasury: None,
+        penalty_decay: 0,
+        pauser: None,
+    };
+
+    let info = mock_info("owner0000", &[]);
+    let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let info = mock_info("addr0000", &[Coin::new(100, "kart0000".to_string())]);
+    let _res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::Stake { lock_tier: None },
+    )
+    .unwrap();
+
+    let info = mock_info("addr0000", &[]);
+    for amount in [60u128, 40u128] {
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            ExecuteMsg::Unstake {
+                amount: Uint128::from(amount),
+            },
+        )
+        .unwrap();
+    }
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        ExecuteMsg::Rebond { id: 2 },
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            msg: "invalid unlock id".to_string()
+        }
+    );
+
+    // the fully exited staker is counted again
+    let _res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        ExecuteMsg::Rebond { id: 1 },
+    )
+    .unwrap();
+    let state = from_json::<StateResponse>(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::State { block_time: None },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(state.total_staker, 1);
+    assert_eq!(state.total_stake_amount, Uint128::from(40u128));
+
+    let _res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::RebondAll {}).unwrap();
+    let staker_info = from_json::<StakerInfoResponse>(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::StakerInfo {
+                staker: "addr0000".to_string(),
+                block_time: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(staker_info.stake_amount, Uint128::from(100u128));
+    assert_eq!(staker_info.unlock, None);
+
+    let balance = from_json::<BalanceResponse>(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Balance {
+                address: "addr0000".to_string(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(balance.balance, Uint128::from(100u128));
+}
+
+#[test]
+fn test_withdraw_all() {
+    let mut deps = mock_dependencies(&[]);
+
+    let msg = InstantiateMsg {
+        kart_denom: "kart0000".to_string(),
+        kart_token_type: TokenType::Native,
+        unlock_time: 10000,
+        distribution_schedule: vec![],
+        drawdown_epoch: 86400,
+        max_drawdown: Decimal::percent(10),
+        receipt_name: "Staked KART".to_string(),
+        receipt_symbol: "sKART".to_string(),
+        receipt_decimals: 6,
+        position_nft: None,
+        boost_collections: vec![],
+        lock_tiers: vec![],
+        instant_unstake_penalty: Decimal::zero(),
+        penalty_treasury: None,
+        penalty_decay: 0,
+        pauser: None,
+    };
+
+    let info = mock_info("owner0000", &[]);
+    let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let info = mock_info("addr0000", &[Coin::new(100, "kart0000".to_string())]);
+    let _res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::Stake { lock_tier: None },
+    )
+    .unwrap();
+
+    let info = mock_info("addr0000", &[]);
+    let mut env = mock_env();
+    for amount in [30u128, 20u128, 10u128] {
+        let _res = execute(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            ExecuteMsg::Unstake {
+                amount: Uint128::from(amount),
+            },
+        )
+        .unwrap();
+        env.block.time = env.block.time.plus_seconds(5000);
+    }
+
+    // ids stay put when an earlier unlock is removed
+    let _res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        ExecuteMsg::Rebond { id: 0 },
+    )
+    .unwrap();
+
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(15000);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info.clone(),
+        ExecuteMsg::WithdrawAll {},
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: "addr0000".to_string(),
+            amount: vec![Coin::new(20, "kart0000".to_string())],
+        })
+    );
+
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info.clone(),
+        ExecuteMsg::Withdraw { id: 1 },
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            msg: "invalid unlock id".to_string()
+        }
+    );
+
+    let staker_info = from_json::<StakerInfoResponse>(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::StakerInfo {
+                staker: "addr0000".to_string(),
+                block_time: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        staker_info.unlock,
+        Some(vec![Unlock {
+            id: 2,
+            amount: Uint128::from(10u128),
+            share: Uint128::from(10u128),
+            at: mock_env().block.time.seconds() + 20000,
+        }])
+    );
+}
+
+#[test]
+fn test_instant_unstake() {
+    let mut deps = mock_dependencies(&[]);
+
+    let msg = InstantiateMsg {
+        kart_denom: "kart0000".to_string(),
+        kart_token_type: TokenType::Native,
+        unlock_time: 10000,
+        distribution_schedule: vec![],
+        drawdown_epoch: 86400,
+        max_drawdown: Decimal::percent(10),
+        receipt_name: "Staked KART".to_string(),
+        receipt_symbol: "sKART".to_string(),
+        receipt_decimals: 6,
+        position_nft: None,
+        boost_collections: vec![],
+        lock_tiers: vec![],
+        instant_unstake_penalty: Decimal::percent(10),
+        penalty_treasury: None,
+        penalty_decay: 100,
+        pauser: None,
+    };
+
+    let info = mock_info("owner0000", &[]);
+    let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    for staker in ["addr0000", "addr0001"] {
+        let info = mock_info(staker, &[Coin::new(100, "kart0000".to_string())]);
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::Stake { lock_tier: None },
+        )
+        .unwrap();
+    }
+
+    // half way through the decay the penalty is halved
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(50);
+    let info = mock_info("addr0000", &[]);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        ExecuteMsg::InstantUnstake {
+            amount: Uint128::from(100u128),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages
+            .into_iter()
+            .map(|m| m.msg)
+            .collect::<Vec<CosmosMsg>>(),
+        vec![CosmosMsg::Bank(BankMsg::Send {
+            to_address: "addr0000".to_string(),
+            amount: vec![Coin::new(95, "kart0000".to_string())],
+        })]
+    );
+
+    let state = from_json::<StateResponse>(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::State { block_time: None },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(state.total_staker, 1);
+    assert_eq!(state.total_stake_amount, Uint128::from(100u128));
+
+    // the penalty is shared by the remaining stakers
+    let staker_info = from_json::<StakerInfoResponse>(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::StakerInfo {
+                staker: "addr0001".to_string(),
+                block_time: Some(env.block.time.seconds()),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        staker_info.pending_reward,
+        HashMap::from([("kart0000".to_string(), Uint128::from(5u128))])
+    );
+}
+
+#[test]
+fn test_ownership_transfer() {
+    let mut deps = mock_dependencies(&[]);
+
+    let msg = InstantiateMsg {
+        kart_denom: "kart0000".to_string(),
+        kart_token_type: TokenType::Native,
+        unlock_time: 10000,
+        distribution_schedule: vec![],
+        drawdown_epoch: 86400,
+        max_drawdown: Decimal::percent(10),
+        receipt_name: "Staked KART".to_string(),
+        receipt_symbol: "sKART".to_string(),
+        receipt_decimals: 6,
+        position_nft: None,
+        boost_collections: vec![],
+        lock_tiers: vec![],
+        instant_unstake_penalty: Decimal::zero(),
+        penalty_treasury: None,
+        penalty_decay: 0,
+        pauser: None,
+    };
+
+    let info = mock_info("owner0000", &[]);
+    let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let msg = ExecuteMsg::ProposeNewOwner {
+        owner: "owner0001".to_string(),
+        expiry: Some(Expiration::AtHeight(mock_env().block.height + 10)),
+    };
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    let info = mock_info("owner0000", &[]);
+    let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let ownership = from_json::<OwnershipResponse>(
+        &query(deps.as_ref(), mock_env(), QueryMsg::Ownership {}).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        ownership,
+        OwnershipResponse {
+            owner: "owner0000".to_string(),
+            pending_owner: Some("owner0001".to_string()),
+            pending_expiry: Some(Expiration::AtHeight(mock_env().block.height + 10)),
+        }
+    );
+
+    // only the proposed owner can accept, and only before the expiry
+    let info = mock_info("addr0000", &[]);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::AcceptOwnership {},
+    )
+    .unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    let mut env = mock_env();
+    env.block.height += 10;
+    let info = mock_info("owner0001", &[]);
+    let res = execute(
+        deps.as_mut(),
+        env,
+        info.clone(),
+        ExecuteMsg::AcceptOwnership {},
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            msg: "ownership proposal expired".to_string()
+        }
+    );
+
+    let _res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::AcceptOwnership {},
+    )
+    .unwrap();
+
+    let ownership = from_json::<OwnershipResponse>(
+        &query(deps.as_ref(), mock_env(), QueryMsg::Ownership {}).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(ownership.owner, "owner0001".to_string());
+    assert_eq!(ownership.pending_owner, None);
+
+    let info = mock_info("owner0000", &[]);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::AddGame {
+            game: "game0000".to_string(),
+            max_exposure: Uint128::from(100u128),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+}
+
+#[test]
+fn test_update_config() {
+    let mut deps = mock_dependencies(&[]);
+
+    let msg = InstantiateMsg {
+        kart_denom: "kart0000".to_string(),
+        kart_token_type: TokenType::Native,
+        unlock_time: 10000,
+        distribution_schedule: vec![],
+        drawdown_epoch: 86400,
+        max_drawdown: Decimal::percent(10),
+        receipt_name: "Staked KART".to_string(),
+        receipt_symbol: "sKART".to_string(),
+        receipt_decimals: 6,
+        position_nft: None,
+        boost_collections: vec![],
+        lock_tiers: vec![],
+        instant_unstake_penalty: Decimal::percent(10),
+        penalty_treasury: None,
+        penalty_decay: 0,
+        pauser: None,
+    };
+
+    let info = mock_info("owner0000", &[]);
+    let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+    let config = Config {
+        kart_denom: "kart0000".to_string(),
+        kart_token_type: TokenType::Native,
+        unlock_time: 5000,
+        drawdown_epoch: 86400,
+        max_drawdown: Decimal::percent(10),
+        position_nft: false,
+        boost_collections: vec![],
+        lock_tiers: vec![],
+        instant_unstake_penalty: Decimal::percent(10),
+        penalty_treasury: None,
+        penalty_decay: 0,
+    };
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        ExecuteMsg::UpdateConfig {
+            config: config.clone(),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    // the instantiate checks apply to updates as well
+    for (invalid, error) in [
+        (
+            Config {
+                drawdown_epoch: 0,
+                ..config.clone()
+            },
+            "drawdown epoch must not be zero",
+        ),
+        (
+            Config {
+                max_drawdown: Decimal::one(),
+                ..config.clone()
+            },
+            "max drawdown must be less than 1",
+        ),
+        (
+            Config {
+                instant_unstake_penalty: Decimal::percent(150),
+                ..config.clone()
+            },
+            "instant unstake penalty must not exceed 1",
+        ),
+        (
+            Config {
+                boost_collections: vec![(Addr::unchecked("nft0000"), Decimal::percent(50))],
+                ..config.clone()
+            },
+            "boost multiplier must be at least 1",
+        ),
+        (
+            Config {
+                kart_denom: "kart0001".to_string(),
+                ..config.clone()
+            },
+            "kart asset and position mode cannot be changed",
+        ),
+        (
+            Config {
+                kart_token_type: TokenType::Cw20,
+                ..config.clone()
+            },
+            "kart asset and position mode cannot be changed",
+        ),
+        (
+            Config {
+                position_nft: true,
+                ..config.clone()
+            },
+            "kart asset and position mode cannot be changed",
+        ),
+    ] {
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            ExecuteMsg::UpdateConfig { config: invalid },
+        )
+        .unwrap_err();
+        assert_eq!(
+            res,
+            ContractError::CustomError {
+                msg: error.to_string()
+            }
+        );
+    }
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        ExecuteMsg::UpdateConfig {
+            config: Config {
+                penalty_treasury: Some(Addr::unchecked("Treasury0000")),
+                ..config.clone()
+            },
+        },
+    )
+    .unwrap_err();
+    assert!(matches!(res, ContractError::Std(_)));
+
+    let _res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::UpdateConfig { config },
+    )
+    .unwrap();
+    let res = from_json::<ConfigResponse>(
+        &query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(res.unlock_time, 5000);
+}
+
+#[test]
+fn test_pause() {
+    let mut deps = mock_dependencies(&[]);
+
+    let msg = InstantiateMsg {
+        kart_denom: "kart0000".to_string(),
+        kart_token_type: TokenType::Native,
+        unlock_time: 10000,
+        distribution_schedule: vec![(
+            mock_env().block.time.seconds(),
+            mock_env().block.time.seconds() + 100,
+            Uint128::from(1000u128),
+            AssetInfo::Native {
+                denom: "usk0000".to_string(),
+            },
+        )],
+        drawdown_epoch: 86400,
+        max_drawdown: Decimal::percent(10),
+        receipt_name: "Staked KART".to_string(),
+        receipt_symbol: "sKART".to_string(),
+        receipt_decimals: 6,
+        position_nft: None,
+        boost_collections: vec![],
+        lock_tiers: vec![],
+        instant_unstake_penalty: Decimal::zero(),
+        penalty_treasury: None,
+        penalty_decay: 0,
+        pauser: Some("pauser0000".to_string()),
+    };
+
+    let info = mock_info("owner0000", &[]);
+    let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let info = mock_info("addr0000", &[Coin::new(100, "kart0000".to_string())]);
+    let _res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::Stake { lock_tier: None },
+    )
+    .unwrap();
+
+    let paused = PauseFlags {
+        unstake: true,
+        claim: true,
+        ..PauseFlags::default()
+    };
+    let info = mock_info("addr0000", &[]);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::SetPaused {
+            paused: paused.clone(),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    let info = mock_info("pauser0000", &[]);
+    let _res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::SetPaused {
+            paused: paused.clone(),
+        },
+    )
+    .unwrap();
+
+    let status = from_json::<PauseStatusResponse>(
+        &query(deps.as_ref(), mock_env(), QueryMsg::PauseStatus {}).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        status,
+        PauseStatusResponse {
+            pauser: Some("pauser0000".to_string()),
+            paused,
+            emergency_withdraw: false,
+        }
+    );
+
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(100);
+    let info = mock_info("addr0000", &[]);
+    for msg in [
+        ExecuteMsg::Unstake {
+            amount: Uint128::from(10u128),
+        },
+        ExecuteMsg::Claim {},
+    ] {
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap_err();
+        assert_eq!(res, ContractError::Paused {});
+    }
+
+    // staking is still open
+    let info = mock_info("addr0001", &[Coin::new(100, "kart0000".to_string())]);
+    let _res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::Stake { lock_tier: None },
+    )
+    .unwrap();
+
+    // rewards kept accruing while claims were paused
+    let info = mock_info("owner0000", &[]);
+    let _res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        ExecuteMsg::SetPaused {
+            paused: PauseFlags::default(),
+        },
+    )
+    .unwrap();
+
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), env, info, ExecuteMsg::Claim {}).unwrap();
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: "addr0000".to_string(),
+            amount: vec![Coin::new(500, "usk0000".to_string())],
+        })
+    );
+}
+
+#[test]
+fn test_claim_paused_exits() {
+    let mut deps = mock_dependencies(&[]);
+
+    let msg = InstantiateMsg {
+        kart_denom: "kart0000".to_string(),
+        kart_token_type: TokenType::Native,
+        unlock_time: 50,
+        distribution_schedule: vec![
+            (
+                mock_env().block.time.seconds(),
+                mock_env().block.time.seconds() + 100,
+                Uint128::from(1000u128),
+                AssetInfo::Native {
+                    denom: "usk0000".to_string(),
+                },
+            ),
+            (
+                mock_env().block.time.seconds(),
+                mock_env().block.time.seconds() + 100,
+                Uint128::from(900u128),
+                AssetInfo::Native {
+                    denom: "kart0000".to_string(),
+                },
+            ),
+        ],
+        drawdown_epoch: 86400,
+        max_drawdown: Decimal::percent(10),
+        receipt_name: "Staked KART".to_string(),
+        receipt_symbol: "sKART".to_string(),
+        receipt_decimals: 6,
+        position_nft: None,
+        boost_collections: vec![],
+        lock_tiers: vec![],
+        instant_unstake_penalty: Decimal::zero(),
+        penalty_treasury: None,
+        penalty_decay: 0,
+        pauser: None,
+    };
+
+    let info = mock_info("owner0000", &[]);
+    let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    for staker in ["addr0000", "addr0001", "addr0002"] {
+        let info = mock_info(staker, &[Coin::new(100, "kart0000".to_string())]);
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::Stake { lock_tier: None },
+        )
+        .unwrap();
+    }
+    let info = mock_info("addr0002", &[]);
+    let _res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::SetAutoCompound { enabled: true },
+    )
+    .unwrap();
+
+    let info = mock_info("owner0000", &[]);
+    let _res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::SetPaused {
+            paused: PauseFlags {
+                claim: true,
+                ..PauseFlags::default()
+            },
+        },
+    )
+    .unwrap();
+
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(50);
+    let info = mock_info("addr0000", &[]);
+    let _res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        ExecuteMsg::Unstake {
+            amount: Uint128::from(100u128),
+        },
+    )
+    .unwrap();
+
+    let staker_info = |deps: &OwnedDeps<_, _, _>, staker: &str| {
+        from_json::<StakerInfoResponse>(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::StakerInfo {
+                    staker: staker.to_string(),
+                    block_time: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap()
+    };
+    let kart_transfer = |staker: &str| {
+        vec![CosmosMsg::Bank(BankMsg::Send {
+            to_address: staker.to_string(),
+            amount: vec![Coin::new(100, "kart0000".to_string())],
+        })]
+    };
+
+    // the full exits only return the principal while claims are paused
+    env.block.time = env.block.time.plus_seconds(50);
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), env.clone(), info, ExecuteMsg::WithdrawAll {}).unwrap();
+    assert_eq!(
+        res.messages.into_iter().map(|m| m.msg).collect::<Vec<_>>(),
+        kart_transfer("addr0000")
+    );
+    assert_eq!(
+        staker_info(&deps, "addr0000").pending_reward,
+        HashMap::from([
+            ("usk0000".to_string(), Uint128::from(166u128)),
+            ("kart0000".to_string(), Uint128::from(150u128)),
+        ])
+    );
+
+    let info = mock_info("addr0001", &[]);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        ExecuteMsg::InstantUnstake {
+            amount: Uint128::from(100u128),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages.into_iter().map(|m| m.msg).collect::<Vec<_>>(),
+        kart_transfer("addr0001")
+    );
+    assert_eq!(
+        staker_info(&deps, "addr0001").pending_reward,
+        HashMap::from([
+            ("usk0000".to_string(), Uint128::from(416u128)),
+            ("kart0000".to_string(), Uint128::from(375u128)),
+        ])
+    );
+
+    // auto-compound leaves the KART rewards pending too
+    let info = mock_info("addr0002", &[]);
+    let _res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        ExecuteMsg::Unstake {
+            amount: Uint128::from(10u128),
+        },
+    )
+    .unwrap();
+    let info = staker_info(&deps, "addr0002");
+    assert_eq!(info.share, Uint128::from(90u128));
+    assert_eq!(
+        info.pending_reward.get("kart0000"),
+        Some(&Uint128::from(375u128))
+    );
+
+    // once claims reopen, the claim pays the rewards and removes the record
+    let info = mock_info("owner0000", &[]);
+    let _res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        ExecuteMsg::SetPaused {
+            paused: PauseFlags::default(),
+        },
+    )
+    .unwrap();
+
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), env, info, ExecuteMsg::Claim {}).unwrap();
+    let mut coins = match &res.messages[..] {
+        [msg] => match &msg.msg {
+            CosmosMsg::Bank(BankMsg::Send { to_address, amount }) => {
+                assert_eq!(to_address, "addr0000");
+                amount.clone()
+            }
+            msg => panic!("unexpected message {:?}", msg),
+        },
+        messages => panic!("unexpected messages {:?}", messages),
+    };
+    coins.sort_by(|a, b| a.denom.cmp(&b.denom));
+    assert_eq!(
+        coins,
+        vec![
+            Coin::new(150, "kart0000".to_string()),
+            Coin::new(166, "usk0000".to_string()),
+        ]
+    );
+    assert!(!USER_STAKING.has(deps.as_ref().storage, "addr0000"));
+}
+
+#[test]
+fn test_claim_paused_position_exits() {
+    let mut deps = mock_dependencies(&[]);
+
+    let msg = InstantiateMsg {
+        kart_denom: "kart0000".to_string(),
+        kart_token_type: TokenType::Native,
+        unlock_time: 50,
+        distribution_schedule: vec![(
+            mock_env().block.time.seconds(),
+            mock_env().block.time.seconds() + 100,
+            Uint128::from(1000u128),
+            AssetInfo::Native {
+                denom: "usk0000".to_string(),
+            },
+        )],
+        drawdown_epoch: 86400,
+        max_drawdown: Decimal::percent(10),
+        receipt_name: "Staked KART".to_string(),
+        receipt_symbol: "sKART".to_string(),
+        receipt_decimals: 6,
+        position_nft: Some(PositionNftInfo {
+            name: "KART Position".to_string(),
+            symbol: "KARTPOS".to_string(),
+        }),
+        boost_collections: vec![],
+        lock_tiers: vec![],
+        instant_unstake_penalty: Decimal::zero(),
+        penalty_treasury: None,
+        penalty_decay: 0,
+        pauser: None,
+    };
+
+    let info = mock_info("owner0000", &[]);
+    let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    for _ in 0..2 {
+        let info = mock_info("addr0000", &[Coin::new(100, "kart0000".to_string())]);
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::Stake { lock_tier: None },
+        )
+        .unwrap();
+    }
+
+    let info = mock_info("owner0000", &[]);
+    let _res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::SetPaused {
+            paused: PauseFlags {
+                claim: true,
+                ..PauseFlags::default()
+            },
+        },
+    )
+    .unwrap();
+
+    let position = |deps: &OwnedDeps<_, _, _>, token_id: &str| {
+        from_json::<StakerInfoResponse>(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::Position {
+                    token_id: token_id.to_string(),
+                    block_time: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap()
+    };
+    let num_tokens = |deps: &OwnedDeps<_, _, _>| {
+        from_json::<NumTokensResponse>(
+            &query(deps.as_ref(), mock_env(), QueryMsg::NumTokens {}).unwrap(),
+        )
+        .unwrap()
+        .count
+    };
+    let kart_transfer = vec![CosmosMsg::Bank(BankMsg::Send {
+        to_address: "addr0000".to_string(),
+        amount: vec![Coin::new(100, "kart0000".to_string())],
+    })];
+
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(50);
+    let info = mock_info("addr0000", &[]);
+    let _res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info.clone(),
+        ExecuteMsg::UnstakePosition {
+            token_id: "1".to_string(),
+            amount: Uint128::from(100u128),
+        },
+    )
+    .unwrap();
+
+    // the emptied positions are kept with their rewards pending
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info.clone(),
+        ExecuteMsg::InstantUnstakePosition {
+            token_id: "2".to_string(),
+            amount: Uint128::from(100u128),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages.into_iter().map(|m| m.msg).collect::<Vec<_>>(),
+        kart_transfer
+    );
+    assert_eq!(
+        position(&deps, "2").pending_reward,
+        HashMap::from([("usk0000".to_string(), Uint128::from(250u128))])
+    );
+
+    env.block.time = env.block.time.plus_seconds(50);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info.clone(),
+        ExecuteMsg::WithdrawAllPosition {
+            token_id: "1".to_string(),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages.into_iter().map(|m| m.msg).collect::<Vec<_>>(),
+        kart_transfer
+    );
+    assert_eq!(
+        position(&deps, "1").pending_reward,
+        HashMap::from([("usk0000".to_string(), Uint128::from(250u128))])
+    );
+    assert_eq!(num_tokens(&deps), 2);
+
+    // once claims reopen, claiming pays an emptied position and burns it
+    let owner = mock_info("owner0000", &[]);
+    let _res = execute(
+        deps.as_mut(),
+        env.clone(),
+        owner,
+        ExecuteMsg::SetPaused {
+            paused: PauseFlags::default(),
+        },
+    )
+    .unwrap();
+
+    let res = execute(
+        deps.as_mut(),
+        env,
+        info,
+        ExecuteMsg::ClaimPosition {
+            token_id: "1".to_string(),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: "addr0000".to_string(),
+            amount: vec![Coin::new(250, "usk0000".to_string())],
+        })
+    );
+    assert_eq!(num_tokens(&deps), 1);
+}
+
+#[test]
+fn test_emergency_withdraw() {
+    let mut deps = mock_dependencies(&[]);
+
+    let msg = InstantiateMsg {
+        kart_denom: "kart0000".to_string(),
+        kart_token_type: TokenType::Native,
+        unlock_time: 10000,
+        distribution_schedule: vec![(
+            mock_env().block.time.seconds(),
+            mock_env().block.time.seconds() + 100,
+            Uint128::from(1000u128),
+            AssetInfo::Native {
+                denom: "usk0000".to_string(),
+            },
+        )],
+        drawdown_epoch: 86400,
+        max_drawdown: Decimal::percent(10),
+        receipt_name: "Staked KART".to_string(),
+        receipt_symbol: "sKART".to_string(),
+        receipt_decimals: 6,
+        position_nft: None,
+        boost_collections: vec![],
+        lock_tiers: vec![],
+        instant_unstake_penalty: Decimal::zero(),
+        penalty_treasury: None,
+        penalty_decay: 0,
+        pauser: None,
+    };
+
+    let info = mock_info("owner0000", &[]);
+    let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    for staker in ["addr0000", "addr0001"] {
+        let info = mock_info(staker, &[Coin::new(100, "kart0000".to_string())]);
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::Stake { lock_tier: None },
+        )
+        .unwrap();
+    }
+
+    let info = mock_info("addr0000", &[]);
+    let _res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        ExecuteMsg::Unstake {
+            amount: Uint128::from(30u128),
+        },
+    )
+    .unwrap();
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        ExecuteMsg::EmergencyWithdraw {},
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            msg: "emergency withdraw is not enabled".to_string()
+        }
+    );
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        ExecuteMsg::SetEmergencyWithdraw { enabled: true },
+    )
+    .unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    let owner = mock_info("owner0000", &[]);
+    let _res = execute(
+        deps.as_mut(),
+        mock_env(),
+        owner,
+        ExecuteMsg::SetEmergencyWithdraw { enabled: true },
+    )
+    .unwrap();
+
+    // settle the first 50 seconds into addr0000's pending rewards before it leaves
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(50);
+    let _res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info.clone(),
+        ExecuteMsg::Unstake {
+            amount: Uint128::from(10u128),
+        },
+    )
+    .unwrap();
+
+    // stake and unlocks come back in one transfer, rewards are forfeited
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        ExecuteMsg::EmergencyWithdraw {},
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages
+            .into_iter()
+            .map(|m| m.msg)
+            .collect::<Vec<CosmosMsg>>(),
+        vec![CosmosMsg::Bank(BankMsg::Send {
+            to_address: "addr0000".to_string(),
+            amount: vec![Coin::new(100, "kart0000".to_string())],
+        })]
+    );
+
+    let state = from_json::<StateResponse>(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::State { block_time: None },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(state.total_staker, 1);
+    assert_eq!(state.total_stake_amount, Uint128::from(100u128));
+    assert_eq!(state.total_share, Uint128::from(100u128));
+    assert_eq!(state.total_weight, Uint128::from(100u128));
+
+    let token_info = from_json::<TokenInfoResponse>(
+        &query(deps.as_ref(), mock_env(), QueryMsg::TokenInfo {}).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(token_info.total_supply, Uint128::from(100u128));
+
+    // the forfeited 205 is no longer owed
+    let res = from_json::<RewardFundingResponse>(
+        &query(deps.as_ref(), mock_env(), QueryMsg::RewardFunding {}).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        res.funding,
+        vec![RewardFunding {
+            asset: AssetInfo::Native {
+                denom: "usk0000".to_string(),
+            },
+            owed: Uint128::from(795u128),
+            balance: Uint128::zero(),
+            shortfall: Uint128::from(795u128),
+        }]
+    );
+}
+
+#[test]
+fn test_emergency_withdraw_broken_checkpoint() {
+    let mut deps = mock_dependencies(&[]);
+
+    let msg = InstantiateMsg {
+        kart_denom: "kart0000".to_string(),
+        kart_token_type: TokenType::Native,
+        unlock_time: 10000,
+        distribution_schedule: vec![(
+            mock_env().block.time.seconds(),
+            mock_env().block.time.seconds() + 100,
+            Uint128::from(1000u128),
+            AssetInfo::Native {
+                denom: "usk0000".to_string(),
+            },
+        )],
+        drawdown_epoch: 86400,
+        max_drawdown: Decimal::percent(10),
+        receipt_name: "Staked KART".to_string(),
+        receipt_symbol: "sKART".to_string(),
+        receipt_decimals: 6,
+        position_nft: None,
+        boost_collections: vec![],
+        lock_tiers: vec![],
+        instant_unstake_penalty: Decimal::zero(),
+        penalty_treasury: None,
+        penalty_decay: 0,
+        pauser: None,
+    };
+
+    let info = mock_info("owner0000", &[]);
+    let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+    let _res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::SetEmergencyWithdraw { enabled: true },
+    )
+    .unwrap();
+
+    let info = mock_info("addr0000", &[Coin::new(100, "kart0000".to_string())]);
+    let _res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::Stake { lock_tier: None },
+    )
+    .unwrap();
+
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(50);
+    let info = mock_info("addr0000", &[]);
+    let _res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info.clone(),
+        ExecuteMsg::Unstake {
+            amount: Uint128::from(30u128),
+        },
+    )
+    .unwrap();
+
+    // a checkpoint above the global index makes every settlement fail
+    USER_REWARDS
+        .save(
+            deps.as_mut().storage,
+            (&Addr::unchecked("addr0000"), "usk0000"),
+            &StakerReward {
+                index: Decimal::from_ratio(1000u128, 1u128),
+                pending: Uint128::from(500u128),
+                claimed: Uint128::zero(),
+            },
+        )
+        .unwrap();
+    let _err = execute(
+        deps.as_mut(),
+        env.clone(),
+        info.clone(),
+        ExecuteMsg::Claim {},
+    )
+    .unwrap_err();
+
+    let res = execute(deps.as_mut(), env, info, ExecuteMsg::EmergencyWithdraw {}).unwrap();
+    assert_eq!(
+        res.messages
+            .into_iter()
+            .map(|m| m.msg)
+            .collect::<Vec<CosmosMsg>>(),
+        vec![CosmosMsg::Bank(BankMsg::Send {
+            to_address: "addr0000".to_string(),
+            amount: vec![Coin::new(100, "kart0000".to_string())],
+        })]
+    );
+
+    let state = from_json::<StateResponse>(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::State { block_time: None },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(state.total_staker, 0);
+    assert_eq!(state.total_stake_amount, Uint128::zero());
+    assert_eq!(state.total_weight, Uint128::zero());
+}
+
+#[test]
+fn test_recover_funds() {
+    let mut deps = mock_dependencies(&[
+        Coin::new(1000, "kart0000".to_string()),
+        Coin::new(500, "usk0000".to_string()),
+    ]);
+
+    let msg = InstantiateMsg {
+        kart_denom: "kart0000".to_string(),
+        kart_token_type: TokenType::Native,
+        unlock_time: 10000,
+        distribution_schedule: vec![(
+            mock_env().block.time.seconds(),
+            mock_env().block.time.seconds() + 100,
+            Uint128::from(300u128),
+            AssetInfo::Native {
+                denom: "usk0000".to_string(),
+            },
+        )],
+        drawdown_epoch: 86400,
+        max_drawdown: Decimal::percent(10),
+        receipt_name: "Staked KART".to_string(),
+        receipt_symbol: "sKART".to_string(),
+        receipt_decimals: 6,
+        position_nft: None,
+        boost_collections: vec![],
+        lock_tiers: vec![],
+        instant_unstake_penalty: Decimal::zero(),
+        penalty_treasury: None,
+        penalty_decay: 0,
+        pauser: None,
+    };
+
+    let info = mock_info("owner0000", &[]);
+    let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let info = mock_info("addr0000", &[Coin::new(100, "kart0000".to_string())]);
+    let _res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::Stake { lock_tier: None },
+    )
+    .unwrap();
+
+    let info = mock_info("addr0000", &[]);
+    let _res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        ExecuteMsg::Unstake {
+            amount: Uint128::from(20u128),
+        },
+    )
+    .unwrap();
+
+    let kart = AssetInfo::Native {
+        denom: "kart0000".to_string(),
+    };
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::RecoverFunds {
+            asset: kart.clone(),
+            amount: Uint128::from(900u128),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    // stake and pending unlocks are reserved
+    let owner = mock_info("owner0000", &[]);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        owner.clone(),
+        ExecuteMsg::RecoverFunds {
+            asset: kart.clone(),
+            amount: Uint128::from(901u128),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(res, ContractError::SurplusExceeded {});
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        owner.clone(),
+        ExecuteMsg::RecoverFunds {
+            asset: kart,
+            amount: Uint128::from(900u128),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: "owner0000".to_string(),
+            amount: vec![Coin::new(900, "kart0000".to_string())],
+        })
+    );
+
+    // so are scheduled rewards that were not paid yet
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        owner,
+        ExecuteMsg::RecoverFunds {
+            asset: AssetInfo::Native {
+                denom: "usk0000".to_string(),
+            },
+            amount: Uint128::from(201u128),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(res, ContractError::SurplusExceeded {});
+}
+
+#[test]
+fn test_reward_funding() {
+    let mut deps = mock_dependencies(&[Coin::new(300, "usk0000".to_string())]);
+
+    let msg = InstantiateMsg {
+        kart_denom: "kart0000".to_string(),
+        kart_token_type: TokenType::Native,
+        unlock_time: 10000,
+        distribution_schedule: vec![(
+            mock_env().block.time.seconds(),
+            mock_env().block.time.seconds() + 100,
+            Uint128::from(500u128),
+            AssetInfo::Native {
+                denom: "usk0000".to_string(),
+            },
+        )],
+        drawdown_epoch: 86400,
+        max_drawdown: Decimal::percent(10),
+        receipt_name: "Staked KART".to_string(),
+        receipt_symbol: "sKART".to_string(),
+        receipt_decimals: 6,
+        position_nft: None,
+        boost_collections: vec![],
+        lock_tiers: vec![],
+        instant_unstake_penalty: Decimal::zero(),
+        penalty_treasury: None,
+        penalty_decay: 0,
+        pauser: None,
+    };
+
+    let info = mock_info("owner0000", &[]);
+    let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let usk = AssetInfo::Native {
+        denom: "usk0000".to_string(),
+    };
+
+    // the schedule amount has to be attached
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("owner0000", &[]),
+        ExecuteMsg::SetDistribution {
+            reward_asset: usk.clone(),
+            start_date: mock_env().block.time.seconds(),
+            end_date: mock_env().block.time.seconds() + 100,
+            amount: Uint128::from(100u128),
+            curve: None,
+        },
+    )
+    .unwrap_err();
+    assert_eq!(res, ContractError::Payment(PaymentError::NoFunds {}));
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("owner0000", &[Coin::new(50, "usk0000".to_string())]),
+        ExecuteMsg::SetDistribution {
+            reward_asset: usk.clone(),
+            start_date: mock_env().block.time.seconds(),
+            end_date: mock_env().block.time.seconds() + 100,
+            amount: Uint128::from(100u128),
+            curve: None,
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            msg: "funds must match the reward amount".to_string(),
+        }
+    );
+
+    // cw20 rewards cannot be set without sending them
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("owner0000", &[]),
+        ExecuteMsg::SetDistribution {
+            reward_asset: AssetInfo::Cw20 {
+                contract_addr: Addr::unchecked("token0000"),
+            },
+            start_date: mock_env().block.time.seconds(),
+            end_date: mock_env().block.time.seconds() + 100,
+            amount: Uint128::from(100u128),
+            curve: None,
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            msg: "cw20 rewards are funded through Receive".to_string(),
+        }
+    );
+
+    // only the owner can fund a cw20 schedule
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("token0000", &[]),
+        ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "addr0000".to_string(),
+            amount: Uint128::from(100u128),
+            msg: to_json_binary(&Cw20HookMsg::SetDistribution {
+                start_date: mock_env().block.time.seconds(),
+                end_date: mock_env().block.time.seconds() + 100,
+                curve: None,
+            })
+            .unwrap(),
+        }),
+    )
+    .unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    // the unfunded instantiate schedule shows up as a shortfall
+    let res = from_json::<RewardFundingResponse>(
+        &query(deps.as_ref(), mock_env(), QueryMsg::RewardFunding {}).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        res.funding,
+        vec![RewardFunding {
+            asset: usk,
+            owed: Uint128::from(500u128),
+            balance: Uint128::from(300u128),
+            shortfall: Uint128::from(200u128),
+        }]
+    );
+}
+
+#[test]
+fn test_schedule_management() {
+    let mut deps = mock_dependencies(&[]);
+
+    let msg = InstantiateMsg {
+        kart_denom: "kart0000".to_string(),
+        kart_token_type: TokenType::Native,
+        unlock_time: 10000,
+        distribution_schedule: vec![(
+            mock_env().block.time.seconds(),
+            mock_env().block.time.seconds() + 100,
+            Uint128::from(1000u128),
+            AssetInfo::Native {
+                denom: "usk0000".to_string(),
+            },
+        )],
+        drawdown_epoch: 86400,
+        max_drawdown: Decimal::percent(10),
+        receipt_name: "Staked KART".to_string(),
+        receipt_symbol: "sKART".to_string(),
+        receipt_decimals: 6,
+        position_nft: None,
+        boost_collections: vec![],
+        lock_tiers: vec![],
+        instant_unstake_penalty: Decimal::zero(),
+        penalty_treasury: None,
+        penalty_decay: 0,
+        pauser: None,
+    };
+
+    let info = mock_info("owner0000", &[]);
+    let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let info = mock_info("addr0000", &[Coin::new(100, "kart0000".to_string())]);
+    let _res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::Stake { lock_tier: None },
+    )
+    .unwrap();
+
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(50);
+
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("addr0000", &[]),
+        ExecuteMsg::ExtendSchedule {
+            id: 0,
+            end_date: mock_env().block.time.seconds() + 150,
+        },
+    )
+    .unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    let owner = mock_info("owner0000", &[]);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        owner.clone(),
+        ExecuteMsg::ExtendSchedule {
+            id: 0,
+            end_date: mock_env().block.time.seconds() + 90,
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            msg: "schedule can only be extended".to_string(),
+        }
+    );
+
+    // the 500 not streamed yet now run until the new end
+    let _res = execute(
+        deps.as_mut(),
+        env.clone(),
+        owner.clone(),
+        ExecuteMsg::ExtendSchedule {
+            id: 0,
+            end_date: mock_env().block.time.seconds() + 150,
+        },
+    )
+    .unwrap();
+
+    let _res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("owner0000", &[Coin::new(500, "usk0000".to_string())]),
+        ExecuteMsg::TopUpSchedule {
+            id: 0,
+            amount: Uint128::from(500u128),
+        },
+    )
+    .unwrap();
+
+    let schedule = from_json::<ScheduleResponse>(
+        &query(deps.as_ref(), env.clone(), QueryMsg::Schedule { id: 0 }).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        schedule,
+        ScheduleResponse {
+            id: 0,
+            start_date: mock_env().block.time.seconds(),
+            end_date: mock_env().block.time.seconds() + 150,
+            amount: Uint128::from(1000u128),
+            asset: AssetInfo::Native {
+                denom: "usk0000".to_string(),
+            },
+            curve: EmissionCurve::Linear,
+            rebased_at: mock_env().block.time.seconds() + 50,
+        }
+    );
+
+    // half of the rebased schedule is streamed, the other half is refunded
+    env.block.time = env.block.time.plus_seconds(50);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        owner,
+        ExecuteMsg::CancelSchedule { id: 0 },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: "owner0000".to_string(),
+            amount: vec![Coin::new(500, "usk0000".to_string())],
+        })
+    );
+
+    let schedules = from_json::<SchedulesResponse>(
+        &query(deps.as_ref(), env.clone(), QueryMsg::Schedules {}).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(schedules.schedules, vec![]);
+
+    env.block.time = env.block.time.plus_seconds(100);
+    let res = execute(
+        deps.as_mut(),
+        env,
+        mock_info("addr0000", &[]),
+        ExecuteMsg::Claim {},
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: "addr0000".to_string(),
+            amount: vec![Coin::new(1000, "usk0000".to_string())],
+        })
+    );
+}
+
+#[test]
+fn test_emission_curve() {
+    let mut deps = mock_dependencies(&[]);
+
+    let msg = InstantiateMsg {
+        kart_denom: "kart0000".to_string(),
+        kart_token_type: TokenType::Native,
+        unlock_time: 10000,
+        distribution_schedule: vec![],
+        drawdown_epoch: 86400,
+        max_drawdown: Decimal::percent(10),
+        receipt_name: "Staked KART".to_string(),
+        receipt_symbol: "sKART".to_string(),
+        receipt_decimals: 6,
+        position_nft: None,
+        boost_collections: vec![],
+        lock_tiers: vec![],
+        instant_unstake_penalty: Decimal::zero(),
+        penalty_treasury: None,
+        penalty_decay: 0,
+        pauser: None,
+    };
+
+    let info = mock_info("owner0000", &[]);
+    let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let info = mock_info("addr0000", &[Coin::new(100, "kart0000".to_string())]);
+    let _res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::Stake { lock_tier: None },
+    )
+    .unwrap();
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("owner0000", &[Coin::new(100, "usk0000".to_string())]),
+        ExecuteMsg::SetDistribution {
+            reward_asset: AssetInfo::Native {
+                denom: "usk0000".to_string(),
+            },
+            start_date: mock_env().block.time.seconds(),
+            end_date: mock_env().block.time.seconds() + 100,
+            amount: Uint128::from(100u128),
+            curve: Some(EmissionCurve::Steps {
+                epoch: 50,
+                rates: vec![],
+            }),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::Std(StdError::generic_err("invalid emission curve"))
+    );
+
+    let schedules = [
+        (
+            "usk0000",
+            3000u128,
+            200u64,
+            EmissionCurve::ExponentialDecay { half_life: 100 },
+        ),
+        (
+            "ukrt0000",
+            1000,
+            100,
+            EmissionCurve::Piecewise {
+                points: vec![(0, Decimal::percent(200)), (100, Decimal::zero())],
+            },
+        ),
+        (
+            "uusd0000",
+            800,
+            100,
+            EmissionCurve::Steps {
+                epoch: 50,
+                rates: vec![Decimal::percent(300), Decimal::percent(100)],
+            },
+        ),
+    ];
+    for (denom, amount, duration, curve) in schedules {
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("owner0000", &[Coin::new(amount, denom.to_string())]),
+            ExecuteMsg::SetDistribution {
+                reward_asset: AssetInfo::Native {
+                    denom: denom.to_string(),
+                },
+                start_date: mock_env().block.time.seconds(),
+                end_date: mock_env().block.time.seconds() + duration,
+                amount: Uint128::from(amount),
+                curve: Some(curve),
+            },
+        )
+        .unwrap();
+    }
+
+    let pending_reward = |deps: &OwnedDeps<_, _, _>, seconds: u64| {
+        from_json::<StakerInfoResponse>(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::StakerInfo {
+                    staker: "addr0000".to_string(),
+                    block_time: Some(mock_env().block.time.seconds() + seconds),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap()
+        .pending_reward
+    };
+
+    // front-loaded curves stream most of their amount in the first half
+    assert_eq!(
+        pending_reward(&deps, 50),
+        HashMap::from([
+            ("usk0000".to_string(), Uint128::from(1171u128)),
+            ("ukrt0000".to_string(), Uint128::from(750u128)),
+            ("uusd0000".to_string(), Uint128::from(600u128)),
+        ])
+    );
+    assert_eq!(
+        pending_reward(&deps, 100),
+        HashMap::from([
+            ("usk0000".to_string(), Uint128::from(2000u128)),
+            ("ukrt0000".to_string(), Uint128::from(1000u128)),
+            ("uusd0000".to_string(), Uint128::from(800u128)),
+        ])
+    );
+}
+
+#[test]
+fn test_undistributed_reward() {
+    let mut deps = mock_dependencies(&[]);
+
+    let msg = InstantiateMsg {
+        kart_denom: "kart0000".to_string(),
+        kart_token_type: TokenType::Native,
+        unlock_time: 10000,
+        distribution_schedule: vec![(
+            mock_env().block.time.seconds(),
+            mock_env().block.time.seconds() + 100,
+            Uint128::from(1000u128),
+            AssetInfo::Native {
+                denom: "usk0000".to_string(),
+            },
+        )],
+        drawdown_epoch: 86400,
+        max_drawdown: Decimal::percent(10),
+        receipt_name: "Staked KART".to_string(),
+        receipt_symbol: "sKART".to_string(),
+        receipt_decimals: 6,
+        position_nft: None,
+        boost_collections: vec![],
+        lock_tiers: vec![],
+        instant_unstake_penalty: Decimal::zero(),
+        penalty_treasury: None,
+        penalty_decay: 0,
+        pauser: None,
+    };
+
+    let info = mock_info("owner0000", &[]);
+    let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    // nobody is staked for the first half of the schedule
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(50);
+    let info = mock_info("addr0000", &[Coin::new(100, "kart0000".to_string())]);
+    let _res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        ExecuteMsg::Stake { lock_tier: None },
+    )
+    .unwrap();
+
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("addr0000", &[]),
+        ExecuteMsg::ReclaimUndistributed {
+            denom: "usk0000".to_string(),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    let owner = mock_info("owner0000", &[]);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        owner.clone(),
+        ExecuteMsg::ReclaimUndistributed {
+            denom: "usk0000".to_string(),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: "owner0000".to_string(),
+            amount: vec![Coin::new(500, "usk0000".to_string())],
+        })
+    );
+
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        owner.clone(),
+        ExecuteMsg::ReclaimUndistributed {
+            denom: "usk0000".to_string(),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            msg: "nothing undistributed".to_string(),
+        }
+    );
+
+    // the last 40 seconds are emitted to nobody again
+    env.block.time = env.block.time.plus_seconds(10);
+    let _res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("addr0000", &[]),
+        ExecuteMsg::Unstake {
+            amount: Uint128::from(100u128),
+        },
+    )
+    .unwrap();
+
+    env.block.time = env.block.time.plus_seconds(40);
+    let _res = execute(
+        deps.as_mut(),
+        env.clone(),
+        owner,
+        ExecuteMsg::RescheduleUndistributed {
+            denom: "usk0000".to_string(),
+            start_date: mock_env().block.time.seconds() + 100,
+            end_date: mock_env().block.time.seconds() + 200,
+            curve: None,
+        },
+    )
+    .unwrap();
+
+    let schedule = from_json::<ScheduleResponse>(
+        &query(deps.as_ref(), env.clone(), QueryMsg::Schedule { id: 1 }).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(schedule.amount, Uint128::from(400u128));
+
+    let state = from_json::<StateResponse>(
+        &query(deps.as_ref(), env, QueryMsg::State { block_time: None }).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        state.reward_distributed,
+        HashMap::from([("usk0000".to_string(), Uint128::from(500u128))])
+    );
+    assert_eq!(state.undistributed, HashMap::from([]));
+}
+
+#[test]
+fn test_migrate() {
+    let mut deps = mock_dependencies(&[]);
+
+    // storage as left by 0.1
+    set_contract_version(&mut deps.storage, "crates.io:kartel_staking", "0.1.0").unwrap();
+    deps.storage.set(
+        b"config",
+        br#"{"owner":"owner0000","kart_denom":"kart0000","unlock_time":10000,"distribution_schedule":[[1571797419,1571797519,"1000","usk0000"]]}"#,
+    );
+    deps.storage.set(
+        b"state",
+        br#"{"total_staker":1,"total_stake_amount":"100","last_distributed":1571797469,"reward_index":{"usk0000":"5"},"reward_distributed":{"usk0000":"1000"}}"#,
+    );
+    deps.storage.set(
+        &USER_STAKING.key("addr0000"),
+        br#"{"stake_amount":"100","pending_reward":{"usk0000":"400"},"reward_index":{"usk0000":"5"},"reward_claimed":{"usk0000":"100"},"unlock":[{"amount":"20","at":1571807419}]}"#,
+    );
+
+    let res = migrate(deps.as_mut(), mock_env(), MigrateMsg { receipt: None }).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::CustomError {
+            msg: "receipt token info is required to migrate from 0.1".to_string(),
+        }
+    );
+
+    let res = migrate(
+        deps.as_mut(),
+        mock_env(),
+        MigrateMsg {
+            receipt: Some(ReceiptTokenInfo {
+                name: "Staked KART".to_string(),
+                symbol: "sKART".to_string(),
+                decimals: 6,
+            }),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "migrate"),
+            attr("from_version", "0.1.0"),
+            attr("to_version", env!("CARGO_PKG_VERSION")),
+        ]
+    );
+
+    let raw_state = String::from_utf8(deps.storage.get(b"state").unwrap()).unwrap();
+    assert!(!raw_state.contains("reward_index"));
+
+    let state = from_json::<StateResponse>(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::State { block_time: None },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(state.total_share, Uint128::from(100u128));
+    assert_eq!(state.total_unlock, Uint128::from(20u128));
+    assert_eq!(state.next_unlock_id, 1);
+    assert_eq!(
+        state.reward_paid,
+        HashMap::from([("usk0000".to_string(), Uint128::from(100u128))])
+    );
+
+    // nobody loses pending rewards and the schedule keeps streaming
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(100);
+    let staker_info = from_json::<StakerInfoResponse>(
+        &query(
+            deps.as_ref(),
+            env,
+            QueryMsg::StakerInfo {
+                staker: "addr0000".to_string(),
+                block_time: Some(mock_env().block.time.seconds() + 100),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(staker_info.share, Uint128::from(100u128));
+    assert_eq!(
+        staker_info.pending_reward,
+        HashMap::from([("usk0000".to_string(), Uint128::from(900u128))])
+    );
+    assert_eq!(
+        staker_info.unlock,
+        Some(vec![Unlock {
+            id: 0,
+            amount: Uint128::from(20u128),
+            share: Uint128::from(20u128),
+            at: 1571807419,
+        }])
+    );
+
+    let balance = from_json::<BalanceResponse>(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Balance {
+                address: "addr0000".to_string(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(balance.balance, Uint128::from(100u128));
+
+    let config = from_json::<ConfigResponse>(
+        &query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(config.owner, "owner0000".to_string());
+
+    // the same version again is a no-op, an older one is refused
+    let _res = migrate(deps.as_mut(), mock_env(), MigrateMsg { receipt: None }).unwrap();
+
+    set_contract_version(&mut deps.storage, "crates.io:kartel_staking", "9.0.0").unwrap();
+    let res = migrate(deps.as_mut(), mock_env(), MigrateMsg { receipt: None }).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::InvalidMigration {
+            contract: "crates.io:kartel_staking".to_string(),
+            version: "9.0.0".to_string(),
+        }
+    );
+
+    set_contract_version(&mut deps.storage, "crates.io:other", "0.1.0").unwrap();
+    let res = migrate(deps.as_mut(), mock_env(), MigrateMsg { receipt: None }).unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::InvalidMigration {
+            contract: "crates.io:other".to_string(),
+            version: "0.1.0".to_string(),
+        }
+    );
+}
+
+#[test]
+fn test_migrate_instantiate_schedule() {
+    let mut deps = mock_dependencies(&[Coin::new(1100, "usk0000".to_string())]);
+
+    // a 0.1 instantiate schedule lives only in config, not in reward_distributed
+    set_contract_version(&mut deps.storage, "crates.io:kartel_staking", "0.1.0").unwrap();
+    deps.storage.set(
+        b"config",
+        br#"{"owner":"owner0000","kart_denom":"kart0000","unlock_time":10000,"distribution_schedule":[[1571797419,1571797519,"1000","usk0000"]]}"#,
+    );
+    deps.storage.set(
+        b"state",
+        br#"{"total_staker":1,"total_stake_amount":"100","last_distributed":1571797419,"reward_index":{},"reward_distributed":{}}"#,
+    );
+    deps.storage.set(
+        &USER_STAKING.key("addr0000"),
+        br#"{"stake_amount":"100","pending_reward":{},"reward_index":{},"reward_claimed":{},"unlock":null}"#,
+    );
+
+    let _res = migrate(
+        deps.as_mut(),
+        mock_env(),
+        MigrateMsg {
+            receipt: Some(ReceiptTokenInfo {
+                name: "Staked KART".to_string(),
+                symbol: "sKART".to_string(),
+                decimals: 6,
+            }),
+        },
+    )
+    .unwrap();
+
+    // the schedule is owed to stakers, only the excess can be recovered
+    let owner = mock_info("owner0000", &[]);
+    let usk = AssetInfo::Native {
+        denom: "usk0000".to_string(),
+    };
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        owner.clone(),
+        ExecuteMsg::RecoverFunds {
+            asset: usk.clone(),
+            amount: Uint128::from(1100u128),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(res, ContractError::SurplusExceeded {});
+
+    let _res = execute(
+        deps.as_mut(),
+        mock_env(),
+        owner,
+        ExecuteMsg::RecoverFunds {
+            asset: usk,
+            amount: Uint128::from(100u128),
+        },
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_all_stakers() {
+    let mut deps = mock_dependencies(&[]);
+
+    let msg = InstantiateMsg {
+        kart_denom: "kart0000".to_string(),
+        kart_token_type: TokenType::Native,
+        unlock_time: 10000,
+        distribution_schedule: vec![(
+            mock_env().block.time.seconds(),
+            mock_env().block.time.seconds() + 100,
+            Uint128::from(300u128),
+            AssetInfo::Native {
+                denom: "usk0000".to_string(),
+            },
+        )],
+        drawdown_epoch: 86400,
+        max_drawdown: Decimal::percent(10),
+        receipt_name: "Staked KART".to_string(),
+        receipt_symbol: "sKART".to_string(),
+        receipt_decimals: 6,
+        position_nft: None,
+        boost_collections: vec![],
+        lock_tiers: vec![],
+        instant_unstake_penalty: Decimal::zero(),
+        penalty_treasury: None,
+        penalty_decay: 0,
+        pauser: None,
+    };
+
+    let info = mock_info("owner0000", &[]);
+    let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    for staker in ["addr0002", "addr0000", "addr0001"] {
+        let info = mock_info(staker, &[Coin::new(100, "kart0000".to_string())]);
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::Stake { lock_tier: None },
+        )
+        .unwrap();
+    }
+
+    let res = from_json::<AllStakersResponse>(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::AllStakers {
+                start_after: None,
+                limit: Some(2),
+                block_time: Some(mock_env().block.time.seconds() + 100),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        res.stakers
+            .iter()
+            .map(|staker| (
+                staker.staker.as_str(),
+                staker.info.stake_amount,
+                staker.info.pending_reward.clone()
+            ))
+            .collect::<Vec<_>>(),
+        vec![
+            (
+                "addr0000",
+                Uint128::from(100u128),
+                HashMap::from([("usk0000".to_string(), Uint128::from(100u128))])
+            ),
+            (
+                "addr0001",
+                Uint128::from(100u128),
+                HashMap::from([("usk0000".to_string(), Uint128::from(100u128))])
+            ),
+        ]
+    );
+
+    let res = from_json::<AllStakersResponse>(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::AllStakers {
+                start_after: Some("addr0001".to_string()),
+                limit: None,
+                block_time: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(res.stakers.len(), 1);
+    assert_eq!(res.stakers[0].staker, "addr0002".to_string());
+    assert_eq!(res.stakers[0].info.pending_reward, HashMap::new());
+}
+
+#[test]
+fn test_top_stakers() {
+    let mut deps = mock_dependencies(&[]);
+
+    let msg = InstantiateMsg {
+        kart_denom: "kart0000".to_string(),
+        kart_token_type: TokenType::Native,
+        unlock_time: 10000,
+        distribution_schedule: vec![],
+        drawdown_epoch: 86400,
+        max_drawdown: Decimal::percent(10),
+        receipt_name: "Staked KART".to_string(),
+        receipt_symbol: "sKART".to_string(),
+        receipt_decimals: 6,
+        position_nft: None,
+        boost_collections: vec![],
+        lock_tiers: vec![],
+        instant_unstake_penalty: Decimal::zero(),
+        penalty_treasury: None,
+        penalty_decay: 0,
+        pauser: None,
+    };
+
+    let info = mock_info("owner0000", &[]);
+    let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    for (staker, amount) in [("addr0000", 100u128), ("addr0001", 300), ("addr0002", 200)] {
+        let info = mock_info(staker, &[Coin::new(amount, "kart0000".to_string())]);
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::Stake { lock_tier: None },
+        )
+        .unwrap();
+    }
+
+    let top_stakers = |deps: &OwnedDeps<_, _, _>, limit, start_after| {
+        from_json::<TopStakersResponse>(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::TopStakers { limit, start_after },
+            )
+            .unwrap(),
+        )
+        .unwrap()
+        .stakers
+        .into_iter()
+        .map(|staker| (staker.staker, staker.info.stake_amount.u128()))
+        .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        top_stakers(&deps, Some(2), None),
+        vec![("addr0001".to_string(), 300), ("addr0002".to_string(), 200)]
+    );
+    assert_eq!(
+        top_stakers(&deps, None, Some("addr0002".to_string())),
+        vec![("addr0000".to_string(), 100)]
+    );
+
+    // the index follows the stake down on unstake
+    let info = mock_info("addr0001", &[]);
+    let _res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::Unstake {
+            amount: Uint128::from(250u128),
+        },
+    )
+    .unwrap();
+
+    assert_eq!(
+        top_stakers(&deps, None, None),
+        vec![
+            ("addr0002".to_string(), 200),
+            ("addr0000".to_string(), 100),
+            ("addr0001".to_string(), 50),
+        ]
+    );
+}
+
+#[test]
+fn test_stake_at_height() {
+    let mut deps = mock_dependencies(&[]);
+
+    let msg = InstantiateMsg {
+        kart_denom: "kart0000".to_string(),
+        kart_token_type: TokenType::Native,
+        unlock_time: 10000,
+        distribution_schedule: vec![],
+        drawdown_epoch: 86400,
+        max_drawdown: Decimal::percent(10),
+        receipt_name: "Staked KART".to_string(),
+        receipt_symbol: "sKART".to_string(),
+        receipt_decimals: 6,
+        position_nft: None,
+        boost_collections: vec![],
+        lock_tiers: vec![],
+        instant_unstake_penalty: Decimal::zero(),
+        penalty_treasury: None,
+        penalty_decay: 0,
+        pauser: None,
+    };
+
+    let info = mock_info("owner0000", &[]);
+    let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let height = mock_env().block.height;
+    for (staker, amount) in [("addr0000", 100u128), ("addr0001", 300)] {
+        let info = mock_info(staker, &[Coin::new(amount, "kart0000".to_string())]);
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::Stake { lock_tier: None },
+        )
+        .unwrap();
+    }
+
+    // voting power moves with the receipt token
+    let mut env = mock_env();
+    env.block.height = height + 10;
+    let info = mock_info("addr0001", &[]);
+    let _res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        ExecuteMsg::Transfer {
+            recipient: "addr0000".to_string(),
+            amount: Uint128::from(100u128),
+        },
+    )
+    .unwrap();
+
+    env.block.height = height + 20;
+    let info = mock_info("addr0000", &[]);
+    let _res = execute(
+        deps.as_mut(),
+        env,
+        info,
+        ExecuteMsg::Unstake {
+            amount: Uint128::from(150u128),
+        },
+    )
+    .unwrap();
+
+    let staked_balance = |deps: &OwnedDeps<_, _, _>, address: &str, height| {
+        from_json::<StakedBalanceAtHeightResponse>(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::StakedBalanceAtHeight {
+                    address: address.to_string(),
+                    height: Some(height),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap()
+        .balance
+        .u128()
+    };
+    let total_staked = |deps: &OwnedDeps<_, _, _>, height| {
+        from_json::<TotalStakedAtHeightResponse>(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::TotalStakedAtHeight {
+                    height: Some(height),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap()
+        .total
+        .u128()
+    };
+
+    // a change is visible from the block after it was made
+    assert_eq!(staked_balance(&deps, "addr0000", height), 0);
+    assert_eq!(total_staked(&deps, height), 0);
+
+    assert_eq!(staked_balance(&deps, "addr0000", height + 1), 100);
+    assert_eq!(staked_balance(&deps, "addr0001", height + 1), 300);
+    assert_eq!(total_staked(&deps, height + 1), 400);
+
+    assert_eq!(staked_balance(&deps, "addr0000", height + 11), 200);
+    assert_eq!(staked_balance(&deps, "addr0001", height + 11), 200);
+    assert_eq!(total_staked(&deps, height + 11), 400);
+
+    assert_eq!(staked_balance(&deps, "addr0000", height + 21), 50);
+    assert_eq!(total_staked(&deps, height + 21), 250);
+
+    // defaults to the current block
+    let res = from_json::<TotalStakedAtHeightResponse>(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::TotalStakedAtHeight { height: None },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(res.height, height);
+}
+
+#[test]
+fn test_position_stake_at_height() {
+    let mut deps = mock_dependencies(&[]);
+
+    let msg = InstantiateMsg {
+        kart_denom: "kart0000".to_string(),
+        kart_token_type: TokenType::Native,
+        unlock_time: 10000,
+        distribution_schedule: vec![],
+        drawdown_epoch: 86400,
+        max_drawdown: Decimal::percent(10),
+        receipt_name: "Staked KART".to_string(),
+        receipt_symbol: "sKART".to_string(),
+        receipt_decimals: 6,
+        position_nft: Some(PositionNftInfo {
+            name: "KART Position".to_string(),
+            symbol: "KARTPOS".to_string(),
+        }),
+        boost_collections: vec![],
+        lock_tiers: vec![],
+        instant_unstake_penalty: Decimal::zero(),
+        penalty_treasury: None,
+        penalty_decay: 0,
+        pauser: None,
+    };
+
+    let info = mock_info("owner0000", &[]);
+    let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let height = mock_env().block.height;
+    for amount in [100u128, 300] {
+        let info = mock_info("addr0000", &[Coin::new(amount, "kart0000".to_string())]);
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::Stake { lock_tier: None },
+        )
+        .unwrap();
+    }
+
+    // the position shares follow the NFT owner
+    let mut env = mock_env();
+    env.block.height = height + 10;
+    let info = mock_info("addr0000", &[]);
+    let msg = ExecuteMsg::TransferNft {
+        recipient: "addr0001".to_string(),
+        token_id: "2".to_string(),
+    };
+    let _res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+    env.block.height = height + 20;
+    let info = mock_info("addr0001", &[]);
+    let msg = ExecuteMsg::UnstakePosition {
+        token_id: "2".to_string(),
+        amount: Uint128::from(100u128),
+    };
+    let _res = execute(deps.as_mut(), env, info, msg).unwrap();
+
+    let staked_balance = |deps: &OwnedDeps<_, _, _>, address: &str, height| {
+        from_json::<StakedBalanceAtHeightResponse>(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::StakedBalanceAtHeight {
+                    address: address.to_string(),
+                    height: Some(height),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap()
+        .balance
+        .u128()
+    };
+    let total_staked = |deps: &OwnedDeps<_, _, _>, height| {
+        from_json::<TotalStakedAtHeightResponse>(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::TotalStakedAtHeight {
+                    height: Some(height),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap()
+        .total
+        .u128()
+    };
+
+    assert_eq!(staked_balance(&deps, "addr0000", height + 1), 400);
+    assert_eq!(total_staked(&deps, height + 1), 400);
+
+    assert_eq!(staked_balance(&deps, "addr0000", height + 11), 100);
+    assert_eq!(staked_balance(&deps, "addr0001", height + 11), 300);
+    assert_eq!(total_staked(&deps, height + 11), 400);
+
+    assert_eq!(staked_balance(&deps, "addr0001", height + 21), 200);
+    assert_eq!(total_staked(&deps, height + 21), 300);
+}
+
+#[test]
+fn test_positions() {
+    let mut deps = mock_dependencies(&[]);
+
+    let msg = InstantiateMsg {
+        kart_denom: "kart0000".to_string(),
+        kart_token_type: TokenType::Native,
+        unlock_time: 10000,
+        distribution_schedule: vec![],
+        drawdown_epoch: 86400,
+        max_drawdown: Decimal::percent(10),
+        receipt_name: "Staked KART".to_string(),
+        receipt_symbol: "sKART".to_string(),
+        receipt_decimals: 6,
+        position_nft: Some(PositionNftInfo {
+            name: "KART Position".to_string(),
+            symbol: "KARTPOS".to_string(),
+        }),
+        boost_collections: vec![],
+        lock_tiers: vec![],
+        instant_unstake_penalty: Decimal::zero(),
+        penalty_treasury: None,
+        penalty_decay: 0,
+        pauser: None,
+    };
+
+    let info = mock_info("owner0000", &[]);
+    let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    for amount in [100u128, 300] {
+        let info = mock_info("addr0000", &[Coin::new(amount, "kart0000".to_string())]);
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::Stake { lock_tier: None },
+        )
+        .unwrap();
+    }
+
+    let info = mock_info("addr0000", &[]);
+    let msg = ExecuteMsg::TransferNft {
+        recipient: "addr0001".to_string(),
+        token_id: "2".to_string(),
+    };
+    let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    // all stake is held in positions, so the staker listing is empty
+    let stakers = from_json::<AllStakersResponse>(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::AllStakers {
+                start_after: None,
+                limit: None,
+                block_time: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert!(stakers.stakers.is_empty());
+
+    let positions = from_json::<PositionsResponse>(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Positions {
+                start_after: None,
+                limit: None,
+                block_time: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        positions
+            .positions
+            .iter()
+            .map(|position| (
+                position.token_id.as_str(),
+                position.owner.as_str(),
+                position.info.stake_amount.u128()
+            ))
+            .collect::<Vec<_>>(),
+        vec![("1", "addr0000", 100), ("2", "addr0001", 300)]
+    );
+
+    let positions = from_json::<PositionsResponse>(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Positions {
+                start_after: Some("1".to_string()),
+                limit: Some(1),
+                block_time: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(positions.positions.len(), 1);
+    assert_eq!(positions.positions[0].token_id, "2");
+}